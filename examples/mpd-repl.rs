@@ -0,0 +1,98 @@
+//! Minimal interactive client using the synchronous API.
+//!
+//! Reads commands from stdin, one per line, sends them to the server and prints a dump of the
+//! response. Input is parsed using the same rules as the server, so arguments containing
+//! whitespace can be quoted using double quotes, and command lists can be entered over several
+//! lines.
+//!
+//! ```sh
+//! cargo run --example mpd-repl -- localhost:6600
+//! ```
+
+use mpd_protocol::command::{parse_client_input, ClientInput, CommandError, RawCommand};
+use mpd_protocol::{sync, CommandList};
+
+use std::env;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader};
+use std::net::TcpStream;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let address = env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("localhost:6600"));
+
+    let mut writer = TcpStream::connect(&address)?;
+    let mut reader = BufReader::new(writer.try_clone()?);
+
+    let version = sync::connect(&mut reader)?;
+    eprintln!("connected to {} (protocol version {})", address, version);
+
+    let mut input = String::new();
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+
+        if input.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+
+        input.push_str(&line);
+        input.push('\n');
+
+        let command = match parse_client_input(input.as_bytes()) {
+            // The command list is not closed yet, read more lines
+            Ok(None) => continue,
+            Ok(Some((parsed, _))) => into_command_list(parsed),
+            Err(e) => {
+                eprintln!("invalid input: {}", e);
+                input.clear();
+                continue;
+            }
+        };
+
+        input.clear();
+
+        let command = match command {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("invalid command: {}", e);
+                continue;
+            }
+        };
+
+        sync::send_list(&mut writer, command)?;
+
+        match sync::receive(&mut reader)? {
+            Some(response) => print!("{}", response.dump()),
+            None => {
+                eprintln!("connection closed");
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert parsed input into a command list, returns `None` for empty lists.
+fn into_command_list(input: ClientInput) -> Result<Option<CommandList>, CommandError> {
+    let commands = match input {
+        ClientInput::Command(command) => vec![command],
+        ClientInput::CommandList { commands, .. } => commands,
+    };
+
+    let mut commands = commands.into_iter().map(RawCommand::into_command);
+
+    let mut list = match commands.next() {
+        Some(first) => CommandList::new(first?),
+        None => return Ok(None),
+    };
+
+    for command in commands {
+        list.add(command?);
+    }
+
+    Ok(Some(list))
+}
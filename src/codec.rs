@@ -2,20 +2,33 @@
 //!
 //! [Codec]: https://docs.rs/tokio-util/0.6.6/tokio_util/codec/index.html
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 use tokio_util::codec::{Decoder, Encoder, Framed};
 use tracing::{debug, error, info, span, Level, Span};
 
+use std::collections::VecDeque;
 use std::io;
+use std::str;
 
-use crate::command::{Command, CommandList};
+use crate::command::{is_sensitive_command, Command, CommandList, CommandWithPayload, REDACTED};
 use crate::parser;
 use crate::response::{Response, ResponseBuilder};
 use crate::MpdProtocolError;
 
+/// Maximum length of the rendered command retained for error context, see
+/// [`MpdCodec::last_command`].
+const LAST_COMMAND_MAX_LEN: usize = 256;
+
 /// [Codec] for MPD protocol.
 ///
+/// # Handing off connections
+///
+/// All state of a partially received response is kept either in the codec or in the read buffer
+/// of the [`Framed`] wrapping it. A connection can therefore be moved to a different task or
+/// IO wrapper at any point by taking it apart using [`Framed::into_parts`] and reassembling it
+/// with [`Framed::from_parts`], as long as both the codec and the read buffer are carried over.
+///
 /// [Codec]: https://docs.rs/tokio-util/0.6.6/tokio_util/codec/index.html
 #[derive(Clone, Debug)]
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
@@ -23,6 +36,10 @@ pub struct MpdCodec {
     log_span: Span,
     current_response: ResponseBuilder,
     protocol_version: Box<str>,
+    last_command: Option<Box<str>>,
+    /// For each encoded command (list) that has not been responded to yet, if it was an `idle`.
+    in_flight: VecDeque<bool>,
+    redact_values: bool,
 }
 
 impl MpdCodec {
@@ -57,6 +74,9 @@ impl MpdCodec {
                         log_span,
                         current_response: ResponseBuilder::new(),
                         protocol_version: version.into(),
+                        last_command: None,
+                        in_flight: VecDeque::new(),
+                        redact_values: false,
                     };
 
                     break Ok(Framed::new(io, codec));
@@ -75,6 +95,114 @@ impl MpdCodec {
     pub fn protocol_version(&self) -> &str {
         &self.protocol_version
     }
+
+    /// Replace the key `alias` with `canonical` in all responses decoded from now on.
+    ///
+    /// This can be used to normalize keys whose spelling differs between server versions, so
+    /// that code matching on them only needs to handle the canonical form. Keys are compared
+    /// case-sensitively.
+    pub fn add_key_alias(&mut self, alias: &str, canonical: &str) {
+        self.current_response.add_key_alias(alias, canonical);
+    }
+
+    /// Enable or disable retaining the raw bytes of decoded responses.
+    ///
+    /// When enabled, the exact bytes each response was decoded from are available using
+    /// [`MpdCodec::get_raw_response`] after it was returned from the decoder. This is disabled by
+    /// default, since it requires copying all received data.
+    pub fn set_retain_raw_responses(&mut self, enabled: bool) {
+        self.current_response.set_retain_raw(enabled);
+    }
+
+    /// Get the raw bytes of the most recently decoded response.
+    ///
+    /// This returns `None` unless enabled using [`MpdCodec::set_retain_raw_responses`]. The bytes
+    /// are removed from the codec, future calls will return `None` until another response has
+    /// been decoded. If they are not retrieved, they are replaced by the next response.
+    pub fn get_raw_response(&mut self) -> Option<Bytes> {
+        self.current_response.take_raw()
+    }
+
+    /// Enable or disable redacting command arguments and response values in log output.
+    ///
+    /// When enabled, the log output of the codec changes as follows:
+    ///
+    /// - Commands are logged with only their names, and the same applies to
+    ///   [`MpdCodec::last_command`].
+    /// - The values of response fields (which may contain e.g. URIs or sticker values) are
+    ///   omitted.
+    /// - Error responses are logged with only their code and command index, without the message
+    ///   or the name of the failed command.
+    ///
+    /// This does not affect the `Debug` output of responses, or of the codec itself (which
+    /// includes a partially received response). Arguments of `password` commands are always
+    /// redacted, regardless of this setting. This is disabled by default.
+    pub fn set_redact_values(&mut self, enabled: bool) {
+        self.redact_values = enabled;
+        self.current_response.set_redact_values(enabled);
+    }
+
+    /// Returns the wire representation of the command that was most recently encoded, if any.
+    ///
+    /// This is intended to provide context when a response fails to parse or contains an error,
+    /// and is also included in the log output in those cases. Long commands (e.g. large command
+    /// lists) are truncated, and arguments are redacted as described in
+    /// [`MpdCodec::set_redact_values`].
+    pub fn last_command(&self) -> Option<&str> {
+        self.last_command.as_deref()
+    }
+
+    /// Get the error context for a rendered command.
+    ///
+    /// If values are redacted, this also logs the command, since the `Debug` output logged
+    /// before rendering would include its arguments.
+    fn logged_command_context(&self, rendered: &[u8]) -> Box<str> {
+        let context = command_context(rendered, self.redact_values);
+
+        if self.redact_values {
+            debug!(command = %context, "encoded command");
+        }
+
+        context
+    }
+}
+
+/// Get the text of a rendered command for use in error context.
+///
+/// The arguments of sensitive commands, or of all commands if `redact_arguments` is set, are
+/// replaced by a placeholder.
+fn command_context(rendered: &[u8], redact_arguments: bool) -> Box<str> {
+    // Rendered commands are always created from strings
+    let rendered = str::from_utf8(rendered).expect("rendered command is not UTF-8");
+    let rendered = rendered.strip_suffix('\n').unwrap_or(rendered);
+
+    let mut context = String::with_capacity(rendered.len());
+    for (i, line) in rendered.split('\n').enumerate() {
+        if i > 0 {
+            context.push('\n');
+        }
+
+        match line.find(' ') {
+            Some(end) if redact_arguments || is_sensitive_command(&line[..end]) => {
+                context.push_str(&line[..end]);
+                context.push(' ');
+                context.push_str(REDACTED);
+            }
+            _ => context.push_str(line),
+        }
+    }
+    let rendered = context.as_str();
+
+    if rendered.len() <= LAST_COMMAND_MAX_LEN {
+        return rendered.into();
+    }
+
+    let mut end = LAST_COMMAND_MAX_LEN;
+    while !rendered.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}...", &rendered[..end]).into()
 }
 
 impl Encoder<Command> for MpdCodec {
@@ -92,9 +220,46 @@ impl Encoder<CommandList> for MpdCodec {
 
     fn encode(&mut self, command: CommandList, buf: &mut BytesMut) -> Result<(), Self::Error> {
         let _enter = self.log_span.enter();
-        debug!(?command, "encoded command");
+        if !self.redact_values {
+            debug!(?command, "encoded command");
+        }
 
+        match command.single_command().map(Command::base) {
+            // These commands do not cause a response of their own
+            Some("noidle") | Some("close") => {}
+            base => self.in_flight.push_back(base == Some("idle")),
+        }
+
+        let start = buf.len();
         command.render(buf);
+        self.last_command = Some(self.logged_command_context(&buf[start..]));
+
+        Ok(())
+    }
+}
+
+impl Encoder<CommandWithPayload> for MpdCodec {
+    type Error = MpdProtocolError;
+
+    fn encode(
+        &mut self,
+        command: CommandWithPayload,
+        buf: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        let _enter = self.log_span.enter();
+        if !self.redact_values {
+            debug!(
+                command = ?command.command(),
+                payload_len = command.payload().len(),
+                "encoded command with payload"
+            );
+        }
+
+        self.in_flight.push_back(false);
+
+        let start = buf.len();
+        let command_len = command.render(buf);
+        self.last_command = Some(self.logged_command_context(&buf[start..start + command_len]));
 
         Ok(())
     }
@@ -106,18 +271,40 @@ impl Decoder for MpdCodec {
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         let _enter = self.log_span.enter();
-        self.current_response.parse(src)
+
+        match self.current_response.parse(src) {
+            Ok(Some(response)) => {
+                self.in_flight.pop_front();
+
+                if response.is_error() {
+                    debug!(last_command = ?self.last_command, "received error response");
+                }
+
+                Ok(Some(response))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                error!(last_command = ?self.last_command, "failed to parse response");
+                Err(e)
+            }
+        }
     }
 
     fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         let _enter = self.log_span.enter();
 
         if !buf.is_empty() || self.current_response.is_frame_in_progress() {
-            error!("EOF while frame in progress");
+            error!(last_command = ?self.last_command, "EOF while frame in progress");
             Err(MpdProtocolError::Io(io::Error::new(
                 io::ErrorKind::UnexpectedEof,
                 "unexpected end of response",
             )))
+        } else if self.in_flight.contains(&true) {
+            // Only report this once, further calls should end the stream
+            self.in_flight.clear();
+
+            error!("EOF while idling");
+            Err(MpdProtocolError::IdleInterrupted)
         } else {
             debug!("EOF while no frame in progress");
             Ok(None)
@@ -132,14 +319,23 @@ mod tests {
     use futures::{sink::SinkExt, stream::StreamExt};
     use std::io::Cursor;
     use tokio_test::io::Builder as MockBuilder;
+    use tokio_util::codec::FramedParts;
 
-    #[test]
-    fn encoder() {
-        let mut codec = MpdCodec {
+    /// A codec in the state directly after connecting.
+    fn test_codec() -> MpdCodec {
+        MpdCodec {
             log_span: Span::none(),
             current_response: ResponseBuilder::new(),
             protocol_version: "".into(),
-        };
+            last_command: None,
+            in_flight: VecDeque::new(),
+            redact_values: false,
+        }
+    }
+
+    #[test]
+    fn encoder() {
+        let mut codec = test_codec();
         let buf = &mut BytesMut::new();
 
         let command = CommandList::new(Command::build("status").unwrap());
@@ -147,6 +343,78 @@ mod tests {
         codec.encode(command, buf).unwrap();
 
         assert_eq!(&b"status\n"[..], buf);
+        assert_eq!(codec.last_command(), Some("status"));
+
+        let command = CommandList::new(Command::new("status")).command(Command::new("stats"));
+        codec.encode(command, buf).unwrap();
+
+        assert_eq!(
+            codec.last_command(),
+            Some("command_list_ok_begin\nstatus\nstats\ncommand_list_end")
+        );
+    }
+
+    #[test]
+    fn encoder_payload() {
+        let mut codec = test_codec();
+        let buf = &mut BytesMut::new();
+
+        let command = Command::new("hello").with_unchecked_payload(&b"\x01\x02"[..]);
+        codec.encode(command, buf).unwrap();
+
+        assert_eq!(&b"hello\n\x01\x02"[..], buf);
+        assert_eq!(codec.last_command(), Some("hello"));
+    }
+
+    #[test]
+    fn redaction() {
+        let mut codec = test_codec();
+        let buf = &mut BytesMut::new();
+
+        let command = CommandList::new(Command::new("password").argument("hunter2"))
+            .command(Command::new("play").argument("1"));
+        codec.encode(command.clone(), buf).unwrap();
+
+        assert_eq!(
+            codec.last_command(),
+            Some("command_list_ok_begin\npassword <redacted>\nplay 1\ncommand_list_end")
+        );
+
+        codec.set_redact_values(true);
+        codec.encode(command, buf).unwrap();
+
+        assert_eq!(
+            codec.last_command(),
+            Some("command_list_ok_begin\npassword <redacted>\nplay <redacted>\ncommand_list_end")
+        );
+    }
+
+    #[tokio::test]
+    async fn handoff() {
+        let mut codec = test_codec();
+        let mut buf = BytesMut::from("foo: bar\nbaz");
+
+        assert_matches!(codec.decode(&mut buf), Ok(None));
+
+        let io = MockBuilder::new().read(b": qux\nOK\n").build();
+        let mut parts = FramedParts::new::<Command>(io, codec);
+        parts.read_buf = buf;
+
+        let response = Framed::from_parts(parts).next().await.unwrap().unwrap();
+        let frame = response.single_frame().unwrap();
+
+        assert_eq!(frame.find("foo"), Some("bar"));
+        assert_eq!(frame.find("baz"), Some("qux"));
+    }
+
+    #[test]
+    fn long_command_context() {
+        let rendered = format!("add {}\n", "ä".repeat(200));
+        let context = command_context(rendered.as_bytes(), false);
+
+        assert!(context.len() <= LAST_COMMAND_MAX_LEN + 3);
+        assert!(context.starts_with("add ä"));
+        assert!(context.ends_with("ä..."));
     }
 
     #[tokio::test]
@@ -185,6 +453,45 @@ mod tests {
         assert_eq!(frame.find("foo"), Some("bar"));
     }
 
+    #[tokio::test]
+    async fn key_aliases() {
+        let io = MockBuilder::new()
+            .read(b"OK MPD 0.21.11\n")
+            .write(b"stats\n")
+            .read(b"DB_Update: 123\nOK\n")
+            .build();
+
+        let mut conn = MpdCodec::connect(io).await.unwrap();
+        conn.codec_mut().add_key_alias("DB_Update", "db_update");
+
+        conn.send(Command::new("stats")).await.unwrap();
+
+        let frame = conn.next().await.unwrap().unwrap().single_frame().unwrap();
+        assert_eq!(frame.find("db_update"), Some("123"));
+        assert_eq!(frame.find("DB_Update"), None);
+    }
+
+    #[tokio::test]
+    async fn raw_responses() {
+        let io = MockBuilder::new()
+            .read(b"OK MPD 0.21.11\n")
+            .write(b"status\n")
+            .read(b"foo: bar\nOK\n")
+            .build();
+
+        let mut conn = MpdCodec::connect(io).await.unwrap();
+        conn.codec_mut().set_retain_raw_responses(true);
+
+        conn.send(Command::new("status")).await.unwrap();
+        conn.next().await.unwrap().unwrap();
+
+        assert_eq!(
+            conn.codec_mut().get_raw_response().unwrap(),
+            &b"foo: bar\nOK\n"[..]
+        );
+        assert_eq!(conn.codec_mut().get_raw_response(), None);
+    }
+
     #[tokio::test]
     async fn eof() {
         let io = MockBuilder::new().read(b"OK MPD 0.21.11\n").build();
@@ -207,4 +514,61 @@ mod tests {
         let mut conn = MpdCodec::connect(io).await.unwrap();
         assert_matches!(conn.next().await, Some(Err(MpdProtocolError::Io(_))));
     }
+
+    #[tokio::test]
+    async fn eof_while_idle_pipelined() {
+        let io = MockBuilder::new()
+            .read(b"OK MPD 0.21.11\n")
+            .write(b"status\nidle\n")
+            .read(b"state: play\nOK\n")
+            .build();
+        let mut conn = MpdCodec::connect(io).await.unwrap();
+        conn.feed(Command::new("status")).await.unwrap();
+        conn.send(Command::new("idle")).await.unwrap();
+
+        assert_matches!(conn.next().await, Some(Ok(_)));
+        assert_matches!(
+            conn.next().await,
+            Some(Err(MpdProtocolError::IdleInterrupted))
+        );
+
+        // The response to `noidle` is the response to the `idle`
+        let mut codec = test_codec();
+        let buf = &mut BytesMut::new();
+        codec.encode(Command::new("idle"), buf).unwrap();
+        codec.encode(Command::new("noidle"), buf).unwrap();
+
+        assert_matches!(codec.decode(&mut BytesMut::from("OK\n")), Ok(Some(_)));
+        assert_matches!(codec.decode_eof(&mut BytesMut::new()), Ok(None));
+    }
+
+    #[tokio::test]
+    async fn eof_while_idle() {
+        let io = MockBuilder::new()
+            .read(b"OK MPD 0.21.11\n")
+            .write(b"idle\n")
+            .build();
+        let mut conn = MpdCodec::connect(io).await.unwrap();
+        conn.send(Command::new("idle")).await.unwrap();
+
+        assert_matches!(
+            conn.next().await,
+            Some(Err(MpdProtocolError::IdleInterrupted))
+        );
+        assert_matches!(conn.next().await, None);
+
+        // Idle completed normally
+        let io = MockBuilder::new()
+            .read(b"OK MPD 0.21.11\n")
+            .write(b"idle player\n")
+            .read(b"changed: player\nOK\n")
+            .build();
+        let mut conn = MpdCodec::connect(io).await.unwrap();
+        conn.send(Command::new("idle").argument("player"))
+            .await
+            .unwrap();
+
+        assert_matches!(conn.next().await, Some(Ok(_)));
+        assert_matches!(conn.next().await, None);
+    }
 }
@@ -2,12 +2,19 @@
 //!
 //! For an overview of available commands, see the [MPD documentation].
 //!
-//! This does not perform any validations on commands beyond checking they appear well-formed, so
-//! it should not be tied to any particular protocol version.
+//! Commands are not validated beyond checking they appear well-formed, so any command can be
+//! sent regardless of the protocol version supported by the server. The exception is
+//! [`Command::class`], which classifies commands using a table of the commands in the current
+//! protocol version, and returns `None` for commands it does not know.
 //!
 //! [MPD documentation]: https://www.musicpd.org/doc/html/protocol.html#command-reference
 
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::dump::CommandDump;
+use crate::parser;
+use crate::response::Toggle;
+use crate::MpdProtocolError;
 
 use std::borrow::Cow;
 use std::error::Error;
@@ -22,6 +29,9 @@ static COMMAND_LIST_BEGIN: &[u8] = b"command_list_ok_begin\n";
 /// End a command list.
 static COMMAND_LIST_END: &[u8] = b"command_list_end\n";
 
+/// Shown in place of arguments which are not included in debug output.
+pub(crate) const REDACTED: &str = "<redacted>";
+
 /// A single command, possibly including arguments.
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Command {
@@ -36,6 +46,39 @@ pub struct CommandList {
     tail: Vec<Command>,
 }
 
+/// A command or command list followed by raw bytes which are sent verbatim.
+///
+/// Created using [`Command::with_unchecked_payload`] or [`CommandList::with_unchecked_payload`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CommandWithPayload {
+    command: CommandList,
+    payload: Bytes,
+}
+
+/// A command as sent by a client, as returned by [`parse_client_input`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawCommand {
+    /// The command name, converted to lowercase.
+    pub name: String,
+    /// The arguments, with quotes and escapes removed.
+    pub arguments: Vec<String>,
+}
+
+/// A complete message sent by a client, as returned by [`parse_client_input`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ClientInput {
+    /// A single command.
+    Command(RawCommand),
+    /// A command list.
+    CommandList {
+        /// If the list was started using `command_list_ok_begin`, i.e. the server will respond
+        /// with a `list_OK` after each command.
+        list_ok: bool,
+        /// The commands contained in the list. This may be empty.
+        commands: Vec<RawCommand>,
+    },
+}
+
 /// Things which can be used as arguments for commands.
 pub trait Argument {
     /// Return the string representation of the argument.
@@ -60,6 +103,20 @@ pub enum CommandError {
     CommandList,
 }
 
+/// The kind of effect a command has on the server, as returned by [`Command::class`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CommandClass {
+    /// The command only reads state, or only affects the current connection (e.g. `status`,
+    /// `find` or `ping`).
+    Query,
+    /// The command modifies the state of the server, such as the queue, playback, stored
+    /// playlists or the database (e.g. `play`, `add` or `update`).
+    Mutating,
+    /// The command affects the server as a whole, and is usually restricted to administrators
+    /// (e.g. `kill` or `mount`).
+    Admin,
+}
+
 impl Command {
     /// Start a new command.
     ///
@@ -108,8 +165,103 @@ impl Command {
         Ok(())
     }
 
+    /// Create a command from untrusted input, such as search strings entered by users.
+    ///
+    /// Unlike [`Command::add_argument`], every argument is always quoted and treated purely as
+    /// data: Leading and trailing whitespace and empty arguments are preserved instead of being
+    /// rejected, and quotes and backslashes are escaped. This ensures that arguments can not
+    /// change the structure of the command, e.g. by closing a quoted section early.
+    ///
+    /// ```
+    /// # use mpd_protocol::Command;
+    /// let command = Command::from_user_input("search", vec!["any", " foo\" bar"]).unwrap();
+    /// assert_eq!(
+    ///     command.dump().to_string(),
+    ///     "   1  search \"any\" \" foo\\\" bar\"\n",
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Errors are returned when the command base is invalid (see [`Command::build`]), or when an
+    /// argument contains a character that can not be represented in the protocol even when quoted
+    /// (newlines and null bytes).
+    pub fn from_user_input<I, A>(
+        command: impl Into<Cow<'static, str>>,
+        arguments: I,
+    ) -> Result<Self, CommandError>
+    where
+        I: IntoIterator<Item = A>,
+        A: Argument,
+    {
+        let mut command = Self::build(command)?;
+
+        for argument in arguments {
+            let argument = argument.render();
+
+            if let Some((i, c)) = argument
+                .char_indices()
+                .find(|&(_, c)| c == '\n' || c == '\0')
+            {
+                return Err(CommandError::InvalidCharacter(i, c));
+            }
+
+            command.args.push(quote_argument(&argument));
+        }
+
+        Ok(command)
+    }
+
+    /// Attach a raw payload which is sent directly after this command.
+    ///
+    /// Same as [`CommandList::with_unchecked_payload`].
+    pub fn with_unchecked_payload(self, payload: impl Into<Bytes>) -> CommandWithPayload {
+        CommandList::new(self).with_unchecked_payload(payload)
+    }
+
+    /// Get a human-readable dump of this command.
+    ///
+    /// See the [`dump`](crate::dump) module for details.
+    pub fn dump(&self) -> CommandDump<'_> {
+        CommandDump::new(self, &[])
+    }
+
+    /// Get the command base (the command name without any arguments).
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Classify the command by its effect on the server.
+    ///
+    /// This uses a table of the commands known to the crate, and returns `None` for unknown
+    /// commands.
+    ///
+    /// ```
+    /// # use mpd_protocol::command::{Command, CommandClass};
+    /// assert_eq!(Command::new("status").class(), Some(CommandClass::Query));
+    /// assert_eq!(Command::new("clear").class(), Some(CommandClass::Mutating));
+    /// assert_eq!(Command::new("kill").class(), Some(CommandClass::Admin));
+    /// assert_eq!(Command::new("foo").class(), None);
+    /// ```
+    pub fn class(&self) -> Option<CommandClass> {
+        let subcommand = self.args.first().map(|a| a.trim_matches('"'));
+        classify(&self.base, subcommand)
+    }
+
+    /// Returns `true` if the command is known to not modify the state of the server.
+    ///
+    /// Unknown commands are not considered read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.class() == Some(CommandClass::Query)
+    }
+
+    /// Get a displayable form of the wire representation, without the terminating newline.
+    pub(crate) fn wire(&self) -> WireCommand<'_> {
+        WireCommand(self)
+    }
+
     /// Get the expected length when this command is rendered to the wire representation
-    fn rendered_length_hint(&self) -> usize {
+    pub(crate) fn rendered_length_hint(&self) -> usize {
         let mut len = self.base.len();
 
         len += self.args.len(); // One separating space for each argument
@@ -133,9 +285,12 @@ impl Command {
 }
 
 impl Debug for Command {
+    /// The arguments of commands containing credentials (i.e. `password`) are redacted.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.args.is_empty() {
             write!(f, "Command({:?})", self.base)
+        } else if is_sensitive_command(&self.base) {
+            write!(f, "Command({:?}, {})", self.base, REDACTED)
         } else {
             write!(f, "Command({:?}, ", self.base)?;
             f.debug_list().entries(&self.args).finish()?;
@@ -144,6 +299,28 @@ impl Debug for Command {
     }
 }
 
+/// Displays the wire representation of a command, as returned by [`Command::wire`].
+///
+/// Like the `Debug` implementation, this redacts the arguments of commands containing
+/// credentials.
+pub(crate) struct WireCommand<'a>(&'a Command);
+
+impl fmt::Display for WireCommand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.base)?;
+
+        if !self.0.args.is_empty() && is_sensitive_command(&self.0.base) {
+            return write!(f, " {}", REDACTED);
+        }
+
+        for arg in &self.0.args {
+            write!(f, " {}", arg)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[allow(clippy::len_without_is_empty)]
 impl CommandList {
     /// Create a command list from the given single command.
@@ -176,6 +353,111 @@ impl CommandList {
         1 + self.tail.len()
     }
 
+    /// Returns `true` if every command in the list is read-only, see [`Command::is_read_only`].
+    pub fn is_read_only(&self) -> bool {
+        iter::once(&self.first)
+            .chain(&self.tail)
+            .all(Command::is_read_only)
+    }
+
+    /// Merge adjacent commands in the list which can be expressed as a single command.
+    ///
+    /// Currently this combines consecutive `delete` commands which remove a contiguous range of
+    /// the queue, taking into account that songs after a deleted position move up. For example,
+    /// `delete 5`, `delete 5` becomes `delete 5:7`, and `delete 7`, `delete 5:7` becomes
+    /// `delete 5:8`. Other commands are left unchanged.
+    ///
+    /// Since merged commands are sent as a single command, the behaviour of the list differs in
+    /// some ways:
+    ///
+    /// - The server responds to each command in a list with a separate frame, so the response to
+    ///   the optimized list may contain fewer frames than the original list.
+    /// - The `command_index` of an [`Error`][crate::response::Error] refers to the position in
+    ///   the optimized list, which may not correspond to the same command in the original list.
+    /// - Failures are no longer partial. For example, on a queue with 6 entries, `delete 5`,
+    ///   `delete 5` deletes one song before failing, while the merged `delete 5:7` fails without
+    ///   deleting anything.
+    ///
+    /// Only use this if these differences do not matter, e.g. for lists generated from a known
+    /// queue state.
+    ///
+    /// ```
+    /// # use mpd_protocol::{Command, CommandList};
+    /// let list = CommandList::new(Command::new("delete").argument("3"))
+    ///     .command(Command::new("delete").argument("2"))
+    ///     .command(Command::new("delete").argument("2"));
+    ///
+    /// assert_eq!(
+    ///     list.optimize(),
+    ///     CommandList::new(Command::new("delete").argument("2:5"))
+    /// );
+    /// ```
+    pub fn optimize(self) -> Self {
+        let mut commands = Vec::with_capacity(self.len());
+        // The range of the pending delete, and the original command if nothing was merged into it
+        let mut pending: Option<((u64, u64), Option<Command>)> = None;
+
+        for command in iter::once(self.first).chain(self.tail) {
+            let range = delete_range(&command);
+
+            match (range, &mut pending) {
+                (Some((start, end)), Some(((s, e), original))) if start == *s => {
+                    *e += end - start;
+                    *original = None;
+                }
+                (Some((start, end)), Some(((s, _), original))) if end == *s => {
+                    *s = start;
+                    *original = None;
+                }
+                (Some(range), _) => {
+                    commands.extend(pending.replace((range, Some(command))).map(finish_delete));
+                }
+                (None, _) => {
+                    commands.extend(pending.take().map(finish_delete));
+                    commands.push(command);
+                }
+            }
+        }
+
+        commands.extend(pending.map(finish_delete));
+
+        let mut commands = commands.into_iter();
+        let mut list = CommandList::new(commands.next().expect("optimized command list is empty"));
+        list.extend(commands);
+        list
+    }
+
+    /// Returns the command if the list consists of only a single command.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn single_command(&self) -> Option<&Command> {
+        if self.tail.is_empty() {
+            Some(&self.first)
+        } else {
+            None
+        }
+    }
+
+    /// Attach a raw payload which is sent directly after this command list.
+    ///
+    /// The payload is **not validated in any way**, it is written to the connection exactly as
+    /// given after the terminating newline of the command (list). This is intended for protocol
+    /// extensions and tests which need to send separately framed data. Since the payload may
+    /// contain newlines, it can be used to send arbitrary further commands, so it must never
+    /// contain untrusted input.
+    pub fn with_unchecked_payload(self, payload: impl Into<Bytes>) -> CommandWithPayload {
+        CommandWithPayload {
+            command: self,
+            payload: payload.into(),
+        }
+    }
+
+    /// Get a human-readable dump of this command list.
+    ///
+    /// See the [`dump`](crate::dump) module for details.
+    pub fn dump(&self) -> CommandDump<'_> {
+        CommandDump::new(&self.first, &self.tail)
+    }
+
     /// Render the command list to the wire representation.
     pub(crate) fn render(self, dst: &mut BytesMut) {
         // If the list only contains a single command, don't wrap it into a command list
@@ -199,6 +481,49 @@ impl CommandList {
     }
 }
 
+impl CommandWithPayload {
+    /// Get the command (list) the payload is attached to.
+    pub fn command(&self) -> &CommandList {
+        &self.command
+    }
+
+    /// Get the raw payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Render the command and payload to the wire representation.
+    ///
+    /// Returns the length of the command part.
+    pub(crate) fn render(self, dst: &mut BytesMut) -> usize {
+        let start = dst.len();
+        self.command.render(dst);
+        let command_len = dst.len() - start;
+
+        dst.extend_from_slice(&self.payload);
+
+        command_len
+    }
+}
+
+impl RawCommand {
+    /// Convert this into a [`Command`] which can be sent to a server.
+    ///
+    /// # Errors
+    ///
+    /// This fails if the command name is not valid, or if an argument contains a newline or a null
+    /// byte, see [`Command::from_user_input`]. Commands returned by [`parse_client_input`] never
+    /// contain such arguments.
+    pub fn into_command(self) -> Result<Command, CommandError> {
+        Command::from_user_input(self.name, self.arguments)
+    }
+
+    /// Classify the command by its effect on the server, see [`Command::class`].
+    pub fn class(&self) -> Option<CommandClass> {
+        classify(&self.name, self.arguments.first().map(String::as_str))
+    }
+}
+
 impl Debug for CommandList {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_list()
@@ -240,6 +565,12 @@ impl Argument for bool {
     }
 }
 
+impl Argument for Toggle {
+    fn render(self) -> Cow<'static, str> {
+        Cow::Borrowed(self.as_str())
+    }
+}
+
 impl Argument for Duration {
     /// Song durations in the format MPD expects. Will round to third decimal place.
     fn render(self) -> Cow<'static, str> {
@@ -294,6 +625,213 @@ fn escape_argument_internal(argument: Cow<'_, str>, enable_quotes: bool) -> Cow<
     }
 }
 
+/// Escape the argument and unconditionally wrap it in quotes.
+fn quote_argument(argument: &str) -> Cow<'static, str> {
+    let escaped = escape_argument_internal(Cow::Borrowed(argument), false);
+
+    let mut out = String::with_capacity(escaped.len() + 2);
+    out.push('"');
+    out.push_str(&escaped);
+    out.push('"');
+
+    Cow::Owned(out)
+}
+
+/// Parse a message sent by a client, such as a command typed by a user or intercepted by a proxy.
+///
+/// This splits the input into the command name and its arguments, and detects command lists.
+/// Arguments may be wrapped in double quotes, and backslash escapes are resolved in both quoted
+/// and unquoted arguments, so this accepts everything rendered by [`Command`].
+/// Unlike the constructors of [`Command`], this does not reject unknown or otherwise suspicious
+/// commands, only input which does not follow the syntax of the protocol.
+///
+/// Returns the parsed message and the number of bytes it consumed, or `Ok(None)` if the input
+/// does not yet contain a complete message (e.g. a command list without the closing
+/// `command_list_end`).
+///
+/// ```
+/// use mpd_protocol::command::{parse_client_input, ClientInput, RawCommand};
+///
+/// let (input, len) = parse_client_input(b"Find \"(Artist == \\\"Foo\\\")\"\n")
+///     .unwrap()
+///     .unwrap();
+///
+/// assert_eq!(len, 27);
+/// assert_eq!(
+///     input,
+///     ClientInput::Command(RawCommand {
+///         name: String::from("find"),
+///         arguments: vec![String::from("(Artist == \"Foo\")")],
+///     })
+/// );
+/// ```
+///
+/// # Errors
+///
+/// This returns [`MpdProtocolError::InvalidMessage`] if the input is malformed, including nested
+/// command lists, a `command_list_end` without a matching start, and arguments containing null
+/// bytes.
+pub fn parse_client_input(input: &[u8]) -> Result<Option<(ClientInput, usize)>, MpdProtocolError> {
+    let (mut rest, first) = match next_raw_command(input)? {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+
+    let list_ok = match &*first.name {
+        "command_list_begin" if first.arguments.is_empty() => false,
+        "command_list_ok_begin" if first.arguments.is_empty() => true,
+        "command_list_end" => return Err(MpdProtocolError::InvalidMessage),
+        _ => {
+            return Ok(Some((
+                ClientInput::Command(first),
+                input.len() - rest.len(),
+            )))
+        }
+    };
+
+    let mut commands = Vec::new();
+
+    loop {
+        let (remaining, command) = match next_raw_command(rest)? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        rest = remaining;
+
+        if is_command_list_command(&command.name) {
+            if command.name == "command_list_end" && command.arguments.is_empty() {
+                break;
+            } else {
+                return Err(MpdProtocolError::InvalidMessage);
+            }
+        }
+
+        commands.push(command);
+    }
+
+    let message = ClientInput::CommandList { list_ok, commands };
+    Ok(Some((message, input.len() - rest.len())))
+}
+
+/// Get the range of positions removed by a `delete` command with a position or range argument.
+///
+/// Positions are limited to `u32` so that merging ranges can not overflow.
+fn delete_range(command: &Command) -> Option<(u64, u64)> {
+    match (&*command.base, command.args.as_slice()) {
+        ("delete", [argument]) => {
+            let argument = argument.trim_matches('"');
+
+            match split_range(argument) {
+                Some((start, end)) => {
+                    let start = u64::from(start.parse::<u32>().ok()?);
+                    let end = u64::from(end.parse::<u32>().ok()?);
+
+                    if start < end {
+                        Some((start, end))
+                    } else {
+                        None
+                    }
+                }
+                None => {
+                    let position = u64::from(argument.parse::<u32>().ok()?);
+                    Some((position, position + 1))
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+fn split_range(argument: &str) -> Option<(&str, &str)> {
+    let i = argument.find(':')?;
+    Some((&argument[..i], &argument[i + 1..]))
+}
+
+/// Turn a pending delete from [`CommandList::optimize`] back into a command.
+fn finish_delete(((start, end), original): ((u64, u64), Option<Command>)) -> Command {
+    original.unwrap_or_else(|| {
+        let argument = if end - start == 1 {
+            start.to_string()
+        } else {
+            format!("{}:{}", start, end)
+        };
+
+        Command::new("delete").argument(argument)
+    })
+}
+
+/// Parse a single command line, returning the remaining input.
+fn next_raw_command(input: &[u8]) -> Result<Option<(&[u8], RawCommand)>, MpdProtocolError> {
+    match parser::client_command(input) {
+        Ok((rest, (name, arguments))) => {
+            let command = RawCommand {
+                name: name.to_ascii_lowercase(),
+                arguments,
+            };
+
+            Ok(Some((rest, command)))
+        }
+        Err(e) if e.is_incomplete() => Ok(None),
+        Err(_) => Err(MpdProtocolError::InvalidMessage),
+    }
+}
+
+/// Classify a command by its name and its first argument (for commands with subcommands).
+fn classify(command: &str, subcommand: Option<&str>) -> Option<CommandClass> {
+    let class = match command {
+        // Settings of the current connection
+        "binarylimit"
+        | "close"
+        | "idle"
+        | "noidle"
+        | "password"
+        | "ping"
+        | "protocol"
+        | "tagtypes"
+        | "stringnormalization"
+        | "partition"
+        | "subscribe"
+        | "unsubscribe"
+        | "readmessages" => CommandClass::Query,
+        // The status and configuration of the server
+        "currentsong" | "status" | "stats" | "getvol" | "replay_gain_status" | "commands"
+        | "notcommands" | "urlhandlers" | "decoders" | "outputs" | "listpartitions"
+        | "channels" | "config" => CommandClass::Query,
+        // The queue and stored playlists
+        "playlist" | "playlistfind" | "playlistid" | "playlistinfo" | "playlistsearch"
+        | "plchanges" | "plchangesposid" | "listplaylist" | "listplaylistinfo"
+        | "listplaylists" | "playlistlength" | "searchplaylist" => CommandClass::Query,
+        // The database
+        "albumart" | "count" | "getfingerprint" | "find" | "list" | "listall" | "listallinfo"
+        | "listfiles" | "lsinfo" | "readcomments" | "readpicture" | "search" | "searchcount"
+        | "listmounts" | "listneighbors" | "stickernames" | "stickertypes"
+        | "stickernamestypes" => CommandClass::Query,
+        "sticker" => match subcommand? {
+            "get" | "list" | "find" => CommandClass::Query,
+            "set" | "delete" | "inc" | "dec" => CommandClass::Mutating,
+            _ => return None,
+        },
+        // Playback and playback options
+        "consume" | "crossfade" | "mixrampdb" | "mixrampdelay" | "random" | "repeat" | "setvol"
+        | "volume" | "single" | "replay_gain_mode" | "next" | "pause" | "play" | "playid"
+        | "previous" | "seek" | "seekid" | "seekcur" | "stop" => CommandClass::Mutating,
+        // Editing the queue and stored playlists
+        "add" | "addid" | "clear" | "delete" | "deleteid" | "move" | "moveid" | "prio"
+        | "prioid" | "rangeid" | "shuffle" | "swap" | "swapid" | "addtagid" | "cleartagid"
+        | "load" | "playlistadd" | "playlistclear" | "playlistdelete" | "playlistmove"
+        | "rename" | "rm" | "save" | "findadd" | "searchadd" | "searchaddpl" => {
+            CommandClass::Mutating
+        }
+        // Other state
+        "clearerror" | "update" | "rescan" | "moveoutput" | "disableoutput" | "enableoutput"
+        | "toggleoutput" | "outputset" | "sendmessage" => CommandClass::Mutating,
+        "kill" | "mount" | "unmount" | "newpartition" | "delpartition" => CommandClass::Admin,
+        _ => return None,
+    };
+
+    Some(class)
+}
+
 /// If the given character needs to be escaped
 fn should_escape(c: char) -> bool {
     c == '\\' || c == '"' || c == '\''
@@ -344,6 +882,11 @@ fn is_valid_command_char(c: char) -> bool {
     c.is_ascii_alphabetic() || c == '_'
 }
 
+/// Returns `true` if the arguments of the given command must never appear in debug output.
+pub(crate) fn is_sensitive_command(command: &str) -> bool {
+    command == "password"
+}
+
 /// Returns `true` if the given command would start or end a command list.
 fn is_command_list_command(command: &str) -> bool {
     command.starts_with("command_list")
@@ -367,6 +910,7 @@ impl fmt::Display for CommandError {
 #[cfg(test)]
 mod test {
     use super::*;
+    use assert_matches::assert_matches;
 
     #[test]
     fn single_render() {
@@ -406,6 +950,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn user_input() {
+        let buf = &mut BytesMut::with_capacity(100);
+
+        Command::from_user_input("search", vec!["any", "foo"])
+            .unwrap()
+            .render(buf);
+        assert_eq!(buf, "search \"any\" \"foo\"\n");
+        buf.clear();
+
+        Command::from_user_input("search", vec!["", " a ", "\" status \"\\"])
+            .unwrap()
+            .render(buf);
+        assert_eq!(buf, "search \"\" \" a \" \"\\\" status \\\"\\\\\"\n");
+        buf.clear();
+
+        assert_eq!(
+            Command::from_user_input("search", vec!["any", "foo\nclear"]).unwrap_err(),
+            CommandError::InvalidCharacter(3, '\n')
+        );
+        assert_eq!(
+            Command::from_user_input("search", vec!["\0"]).unwrap_err(),
+            CommandError::InvalidCharacter(0, '\0')
+        );
+        assert_eq!(
+            Command::from_user_input("search any", Vec::<String>::new()).unwrap_err(),
+            CommandError::InvalidCharacter(6, ' ')
+        );
+    }
+
     #[test]
     fn command_list_render() {
         let buf = &mut BytesMut::with_capacity(100);
@@ -425,6 +999,16 @@ mod test {
         buf.clear();
     }
 
+    #[test]
+    fn payload_render() {
+        let buf = &mut BytesMut::with_capacity(100);
+
+        let command = Command::new("hello").with_unchecked_payload(&b"\x00\xffraw\n"[..]);
+        assert_eq!(command.payload(), b"\x00\xffraw\n");
+        assert_eq!(command.render(buf), 6);
+        assert_eq!(buf, &b"hello\n\x00\xffraw\n"[..]);
+    }
+
     #[test]
     fn argument_escaping() {
         assert_eq!(escape_argument("status"), "status");
@@ -439,7 +1023,222 @@ mod test {
         assert_eq!(true.render(), "1");
         assert_eq!(false.render(), "0");
 
+        assert_eq!(Toggle::Oneshot.render(), "oneshot");
+
         assert_eq!(Duration::from_secs(2).render(), "2.000");
         assert_eq!(Duration::from_secs_f64(2.34567).render(), "2.346");
     }
+
+    #[test]
+    fn client_input() {
+        let raw = |name: &str, arguments: &[&str]| RawCommand {
+            name: String::from(name),
+            arguments: arguments.iter().map(|&a| String::from(a)).collect(),
+        };
+
+        assert_eq!(
+            parse_client_input(b"STATUS\nplay\n").unwrap(),
+            Some((ClientInput::Command(raw("status", &[])), 7))
+        );
+
+        let list = b"command_list_ok_begin\nfoo \"a b\" c\\'d\ncommand_list_end\n";
+        assert_eq!(
+            parse_client_input(list).unwrap(),
+            Some((
+                ClientInput::CommandList {
+                    list_ok: true,
+                    commands: vec![raw("foo", &["a b", "c'd"])],
+                },
+                list.len()
+            ))
+        );
+
+        assert_eq!(
+            parse_client_input(b"command_list_begin\ncommand_list_end\n").unwrap(),
+            Some((
+                ClientInput::CommandList {
+                    list_ok: false,
+                    commands: vec![],
+                },
+                36
+            ))
+        );
+
+        assert_eq!(parse_client_input(b"").unwrap(), None);
+        assert_eq!(parse_client_input(b"play \"foo").unwrap(), None);
+        assert_eq!(
+            parse_client_input(b"command_list_begin\nstatus\n").unwrap(),
+            None
+        );
+
+        assert_matches!(
+            parse_client_input(b"command_list_end\n"),
+            Err(MpdProtocolError::InvalidMessage)
+        );
+        assert_matches!(
+            parse_client_input(b"command_list_begin\ncommand_list_ok_begin\n"),
+            Err(MpdProtocolError::InvalidMessage)
+        );
+        assert_matches!(
+            parse_client_input(b"foo bar\"\n"),
+            Err(MpdProtocolError::InvalidMessage)
+        );
+        assert_matches!(
+            parse_client_input(b"find \"a\\\nclear\"\n"),
+            Err(MpdProtocolError::InvalidMessage)
+        );
+        assert_matches!(
+            parse_client_input(b"find \"a\0b\"\n"),
+            Err(MpdProtocolError::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn raw_command_conversion() {
+        let raw = RawCommand {
+            name: String::from("find"),
+            arguments: vec![String::from("(Artist == \"Foo\")")],
+        };
+
+        assert_eq!(
+            raw.into_command(),
+            Ok(Command::new("find").argument("(Artist == \"Foo\")"))
+        );
+    }
+
+    #[test]
+    fn classification() {
+        assert_eq!(Command::new("listall").class(), Some(CommandClass::Query));
+        assert_eq!(
+            Command::new("setvol").argument("50").class(),
+            Some(CommandClass::Mutating)
+        );
+        assert_eq!(Command::new("mount").class(), Some(CommandClass::Admin));
+        assert!(Command::new("config").is_read_only());
+        assert!(Command::new("partition").argument("foo").is_read_only());
+        assert!(Command::new("playlistlength")
+            .argument("foo")
+            .is_read_only());
+        assert_eq!(Command::new("foo").class(), None);
+
+        let sticker = |subcommand| {
+            Command::from_user_input("sticker", vec![subcommand, "song", "foo"]).unwrap()
+        };
+        assert!(sticker("get").is_read_only());
+        assert_eq!(sticker("set").class(), Some(CommandClass::Mutating));
+        assert_eq!(sticker("inc").class(), Some(CommandClass::Mutating));
+        assert_eq!(sticker("bar").class(), None);
+        assert_eq!(Command::new("sticker").class(), None);
+
+        assert!(Command::new("status").is_read_only());
+        assert!(!Command::new("play").is_read_only());
+        assert!(!Command::new("foo").is_read_only());
+
+        let list = CommandList::new(Command::new("status")).command(Command::new("currentsong"));
+        assert!(list.is_read_only());
+        assert!(!list.command(Command::new("stop")).is_read_only());
+    }
+
+    #[test]
+    fn optimize() {
+        let delete = |argument| Command::new("delete").argument(argument);
+
+        let list = CommandList::new(delete("5"))
+            .command(delete("5"))
+            .command(delete("5:7"))
+            .command(delete("4"))
+            .command(Command::new("status"))
+            .command(delete("1"))
+            .command(delete("3"))
+            .command(delete("9:9"))
+            .command(delete("9:9"));
+
+        assert_eq!(
+            list.optimize(),
+            CommandList::new(delete("4:9"))
+                .command(Command::new("status"))
+                .command(delete("1"))
+                .command(delete("3"))
+                .command(delete("9:9"))
+                .command(delete("9:9"))
+        );
+
+        let list = CommandList::new(Command::new("delete").argument("1").argument("2"))
+            .command(Command::from_user_input("delete", vec!["1"]).unwrap());
+        assert_eq!(list.clone().optimize(), list);
+    }
+
+    #[test]
+    fn password_redaction() {
+        let command = Command::new("password").argument("hunter2");
+
+        assert_eq!(
+            format!("{:?}", command),
+            "Command(\"password\", <redacted>)"
+        );
+        assert_eq!(command.dump().to_string(), "   1  password <redacted>\n");
+        assert!(!format!("{:?}", CommandList::new(command)).contains("hunter2"));
+
+        assert_eq!(
+            format!("{:?}", Command::new("play").argument("1")),
+            "Command(\"play\", [\"1\"])"
+        );
+    }
+
+    /// Every combination of up to three of these is used as an argument in the round trip tests.
+    const ARGUMENT_PIECES: &[&str] =
+        &["a", " ", "\t", "\n", "\"", "'", "\\", "ä", "x y", "{}", "-"];
+
+    fn arguments() -> impl Iterator<Item = String> {
+        let pieces = || ARGUMENT_PIECES.iter().copied();
+
+        pieces()
+            .flat_map(move |a| pieces().map(move |b| (a, b)))
+            .flat_map(move |(a, b)| pieces().map(move |c| format!("{}{}{}", a, b, c)))
+            .chain(pieces().map(String::from))
+    }
+
+    fn round_trip(command: Command) -> RawCommand {
+        let buf = &mut BytesMut::new();
+        CommandList::new(command).render(buf);
+
+        match parse_client_input(buf) {
+            Ok(Some((ClientInput::Command(raw), len))) if len == buf.len() => raw,
+            other => panic!("{:?} did not parse back: {:?}", buf, other),
+        }
+    }
+
+    #[test]
+    fn render_parse_round_trip() {
+        for argument in arguments() {
+            if argument.contains('\n') {
+                // Newlines can not be represented, arguments containing them must be rejected
+                assert!(Command::from_user_input("foo", vec![argument.clone()]).is_err());
+                assert!(Command::new("foo").add_argument(argument).is_err());
+                continue;
+            }
+
+            let command = Command::from_user_input("foo", vec![argument.clone()]).unwrap();
+            assert_eq!(round_trip(command).arguments, [argument.as_str()]);
+
+            let trimmed = argument.trim_matches(&[' ', '\t'][..]);
+            if !trimmed.is_empty() && trimmed == argument {
+                let command = Command::new("foo").argument(argument.clone()).argument("b");
+                assert_eq!(round_trip(command).arguments, [argument.as_str(), "b"]);
+            }
+        }
+
+        let command = Command::from_user_input("foo", vec![""]).unwrap();
+        assert_eq!(round_trip(command).arguments, [""]);
+
+        // An escaped newline must not join two lines into a single command
+        assert_matches!(
+            parse_client_input(b"find a\\\nclear\n"),
+            Err(MpdProtocolError::InvalidMessage)
+        );
+        assert_matches!(
+            parse_client_input(b"find \"a\\\nclear\"\n"),
+            Err(MpdProtocolError::InvalidMessage)
+        );
+    }
 }
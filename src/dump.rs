@@ -0,0 +1,191 @@
+//! Human-readable dumps of commands and responses.
+//!
+//! The types in this module implement [`Display`][fmt::Display], rendering their contents in an
+//! annotated form that closely follows the wire representation, with one numbered line per
+//! protocol line. Binary sections are summarized by their length and a hash instead of being
+//! printed, which makes the output suitable for bug reports and debug logs.
+//!
+//! ```
+//! use mpd_protocol::Command;
+//!
+//! let command = Command::new("find").argument("(Artist == \"Foo\")");
+//!
+//! assert_eq!(
+//!     command.dump().to_string(),
+//!     "   1  find \"(Artist == \\\"Foo\\\")\"\n",
+//! );
+//! ```
+
+use std::fmt;
+use std::iter;
+
+use crate::command::Command;
+use crate::hash::hash_bytes;
+use crate::response::{Error, Frame, Response};
+
+/// Dump of a [`Command`] or [`CommandList`][crate::CommandList], as returned by
+/// [`Command::dump`] and [`CommandList::dump`][crate::CommandList::dump].
+#[derive(Clone, Copy, Debug)]
+pub struct CommandDump<'a> {
+    first: &'a Command,
+    tail: &'a [Command],
+}
+
+/// Dump of a [`Response`], as returned by [`Response::dump`].
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseDump<'a> {
+    response: &'a Response,
+}
+
+impl<'a> CommandDump<'a> {
+    pub(crate) fn new(first: &'a Command, tail: &'a [Command]) -> Self {
+        Self { first, tail }
+    }
+}
+
+impl<'a> ResponseDump<'a> {
+    pub(crate) fn new(response: &'a Response) -> Self {
+        Self { response }
+    }
+}
+
+/// Writes numbered lines.
+struct Lines<'a, 'f> {
+    f: &'a mut fmt::Formatter<'f>,
+    line: usize,
+}
+
+impl<'a, 'f> Lines<'a, 'f> {
+    fn new(f: &'a mut fmt::Formatter<'f>) -> Self {
+        Self { f, line: 0 }
+    }
+
+    fn line(&mut self, content: fmt::Arguments<'_>) -> fmt::Result {
+        self.line += 1;
+        writeln!(self.f, "{:>4}  {}", self.line, content)
+    }
+}
+
+impl fmt::Display for CommandDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Lines::new(f);
+
+        if self.tail.is_empty() {
+            return lines.line(format_args!("{}", self.first.wire()));
+        }
+
+        lines.line(format_args!("command_list_ok_begin"))?;
+
+        for (i, command) in iter::once(self.first).chain(self.tail).enumerate() {
+            lines.line(format_args!("{}  # command {}", command.wire(), i))?;
+        }
+
+        lines.line(format_args!("command_list_end"))
+    }
+}
+
+impl fmt::Display for ResponseDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Lines::new(f);
+
+        let response = self.response;
+        let is_list = response.successful_frames() > 1
+            || (response.is_error() && response.successful_frames() > 0);
+
+        for (i, frame) in response.frames().enumerate() {
+            match frame {
+                Ok(frame) => {
+                    dump_frame(&mut lines, frame)?;
+
+                    if is_list {
+                        lines.line(format_args!("list_OK  # end of frame {}", i))?;
+                    }
+                }
+                Err(error) => return dump_error(&mut lines, error),
+            }
+        }
+
+        lines.line(format_args!("OK"))
+    }
+}
+
+fn dump_frame(lines: &mut Lines<'_, '_>, frame: &Frame) -> fmt::Result {
+    for (key, value) in frame.fields() {
+        lines.line(format_args!("{}: {}", key, value))?;
+    }
+
+    if let Some(binary) = frame.binary() {
+        lines.line(format_args!(
+            "binary: {}  # {} bytes, FNV-1a {:016x}",
+            binary.len(),
+            binary.len(),
+            hash_bytes(binary),
+        ))?;
+    }
+
+    Ok(())
+}
+
+fn dump_error(lines: &mut Lines<'_, '_>, error: &Error) -> fmt::Result {
+    lines.line(format_args!(
+        "ACK [{}@{}] {{{}}} {}  # error in command {}",
+        error.code,
+        error.command_index,
+        error.current_command.as_deref().unwrap_or(""),
+        error.message,
+        error.command_index,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::CommandList;
+    use crate::response::ResponseBuilder;
+    use bytes::BytesMut;
+
+    fn parse(raw: &str) -> Response {
+        ResponseBuilder::new()
+            .parse(&mut BytesMut::from(raw))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn command() {
+        let command = Command::new("status");
+        assert_eq!(command.dump().to_string(), "   1  status\n");
+
+        let list = CommandList::new(Command::new("status"))
+            .command(Command::new("play").argument("foo bar"));
+        assert_eq!(
+            list.dump().to_string(),
+            "   1  command_list_ok_begin\n\
+             \x20  2  status  # command 0\n\
+             \x20  3  play \"foo bar\"  # command 1\n\
+             \x20  4  command_list_end\n"
+        );
+    }
+
+    #[test]
+    fn response() {
+        let response = parse("foo: bar\nbinary: 6\nFOOBAR\nOK\n");
+        assert_eq!(
+            response.dump().to_string(),
+            "   1  foo: bar\n\
+             \x20  2  binary: 6  # 6 bytes, FNV-1a ed91fadfa4bbf528\n\
+             \x20  3  OK\n"
+        );
+
+        let response = parse("list_OK\nfoo: bar\nlist_OK\nACK [5@2] {} unknown command \"x\"\n");
+        assert_eq!(
+            response.dump().to_string(),
+            "   1  list_OK  # end of frame 0\n\
+             \x20  2  foo: bar\n\
+             \x20  3  list_OK  # end of frame 1\n\
+             \x20  4  ACK [5@2] {} unknown command \"x\"  # error in command 2\n"
+        );
+
+        assert_eq!(parse("OK\n").dump().to_string(), "   1  OK\n");
+    }
+}
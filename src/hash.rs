@@ -0,0 +1,62 @@
+//! Stable hashing of response contents.
+
+use std::hash::Hasher;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a hasher.
+///
+/// Unlike the standard library's `DefaultHasher`, the output of this is guaranteed to be the same
+/// across program runs and releases, so it can be shown to users and compared between processes.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Fnv1a(FNV_OFFSET_BASIS)
+    }
+
+    /// Write a number in a platform-independent way.
+    pub(crate) fn write_number(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    /// Write a length-prefixed byte string, so that adjacent values can not be confused.
+    pub(crate) fn write_prefixed(&mut self, bytes: &[u8]) {
+        self.write_number(bytes.len() as u64);
+        self.write(bytes);
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hash the given bytes.
+pub(crate) fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_values() {
+        assert_eq!(hash_bytes(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_bytes(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash_bytes(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
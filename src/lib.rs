@@ -4,7 +4,7 @@
     rust_2018_idioms,
     unreachable_pub
 )]
-#![deny(broken_intra_doc_links)]
+#![deny(rustdoc::broken_intra_doc_links)]
 #![forbid(unsafe_code)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
@@ -26,9 +26,13 @@ use std::io;
 mod codec;
 
 pub mod command;
+pub mod dump;
+pub mod playlist;
 pub mod response;
 pub mod sync;
+pub mod uri;
 
+mod hash;
 mod parser;
 
 #[cfg(feature = "async")]
@@ -44,6 +48,12 @@ pub enum MpdProtocolError {
     Io(io::Error),
     /// A message could not be parsed succesfully.
     InvalidMessage,
+    /// The connection was closed while an `idle` command was waiting for a response.
+    ///
+    /// This most commonly happens when the server is restarted, so it is usually appropriate to
+    /// reconnect and resume idling. Commands sent before the `idle` (e.g. when pipelining) are
+    /// taken into account, as long as they are all sent through the same codec.
+    IdleInterrupted,
 }
 
 impl fmt::Display for MpdProtocolError {
@@ -51,6 +61,7 @@ impl fmt::Display for MpdProtocolError {
         match self {
             MpdProtocolError::Io(_) => write!(f, "IO error"),
             MpdProtocolError::InvalidMessage => write!(f, "invalid message"),
+            MpdProtocolError::IdleInterrupted => write!(f, "connection closed while idling"),
         }
     }
 }
@@ -5,11 +5,13 @@ use nom::{
     bytes::streaming::{tag, take, take_while, take_while1},
     character::{
         is_alphabetic,
-        streaming::{char, digit1, newline},
+        streaming::{char, digit1, newline, space0, space1},
     },
     combinator::{cut, map, map_res, opt},
-    sequence::{delimited, separated_pair, terminated, tuple},
-    IResult,
+    error::{make_error, ErrorKind},
+    multi::many0,
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    Err as NomErr, IResult, Needed,
 };
 
 use std::str::{self, from_utf8, FromStr};
@@ -147,6 +149,78 @@ fn binary_field(i: &[u8]) -> IResult<&[u8], &[u8]> {
     cut(terminated(take(length), newline))(i)
 }
 
+/// Recognize a command line sent by a client, returning the command name and its arguments.
+pub(crate) fn client_command(i: &[u8]) -> IResult<&[u8], (&str, Vec<String>)> {
+    terminated(
+        pair(
+            map_res(take_while1(|b| is_alphabetic(b) || b == b'_'), from_utf8),
+            many0(preceded(space1, alt((quoted_argument, unquoted_argument)))),
+        ),
+        pair(space0, newline),
+    )(i)
+}
+
+/// Recognize an argument without quotes, resolving backslash escapes
+fn unquoted_argument(i: &[u8]) -> IResult<&[u8], String> {
+    let mut rest = i;
+    let mut out = Vec::new();
+
+    loop {
+        match rest {
+            [] | [b'\\'] => return Err(NomErr::Incomplete(Needed::new(1))),
+            [b' ', ..] | [b'\t', ..] | [b'\n', ..] | [b'"', ..] => break,
+            // Arguments can not span multiple lines (not even by escaping the newline), and can
+            // not contain null bytes
+            [b'\\', b'\n', ..] | [b'\0', ..] | [b'\\', b'\0', ..] => {
+                return Err(NomErr::Failure(make_error(rest, ErrorKind::Char)))
+            }
+            [b'\\', c, tail @ ..] | [c, tail @ ..] => {
+                out.push(*c);
+                rest = tail;
+            }
+        }
+    }
+
+    if rest.len() == i.len() {
+        return Err(NomErr::Error(make_error(i, ErrorKind::TakeWhile1)));
+    }
+
+    match String::from_utf8(out) {
+        Ok(argument) => Ok((rest, argument)),
+        Err(_) => Err(NomErr::Failure(make_error(i, ErrorKind::MapRes))),
+    }
+}
+
+/// Recognize an argument in double quotes, resolving backslash escapes
+fn quoted_argument(i: &[u8]) -> IResult<&[u8], String> {
+    let (mut rest, _) = char('"')(i)?;
+    let mut out = Vec::new();
+
+    loop {
+        match rest {
+            [] | [b'\\'] => return Err(NomErr::Incomplete(Needed::new(1))),
+            [b'"', tail @ ..] => {
+                rest = tail;
+                break;
+            }
+            // Arguments can not span multiple lines (not even by escaping the newline), and can
+            // not contain null bytes
+            [b'\n', ..] | [b'\\', b'\n', ..] | [b'\0', ..] | [b'\\', b'\0', ..] => {
+                return Err(NomErr::Failure(make_error(rest, ErrorKind::Char)))
+            }
+            [b'\\', c, tail @ ..] | [c, tail @ ..] => {
+                out.push(*c);
+                rest = tail;
+            }
+        }
+    }
+
+    match String::from_utf8(out) {
+        Ok(argument) => Ok((rest, argument)),
+        Err(_) => Err(NomErr::Failure(make_error(i, ErrorKind::MapRes))),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -252,6 +326,77 @@ mod test {
             .is_incomplete());
     }
 
+    #[test]
+    fn client_command() {
+        assert_eq!(
+            super::client_command(b"status\n"),
+            Ok((EMPTY, ("status", vec![])))
+        );
+
+        assert_eq!(
+            super::client_command(b"find \"(Artist == \\\"Foo\\\")\" sort -Title \n"),
+            Ok((
+                EMPTY,
+                (
+                    "find",
+                    vec![
+                        String::from("(Artist == \"Foo\")"),
+                        String::from("sort"),
+                        String::from("-Title"),
+                    ]
+                )
+            ))
+        );
+
+        assert_eq!(
+            super::client_command(b"search any \"\"\nstatus\n"),
+            Ok((
+                &b"status\n"[..],
+                ("search", vec![String::from("any"), String::new()])
+            ))
+        );
+
+        assert_eq!(
+            super::client_command(b"add Joe\\'s\\ Song\n"),
+            Ok((EMPTY, ("add", vec![String::from("Joe's Song")])))
+        );
+
+        assert!(super::client_command(b"status")
+            .unwrap_err()
+            .is_incomplete());
+        assert!(super::client_command(b"play \"foo")
+            .unwrap_err()
+            .is_incomplete());
+        assert!(super::client_command(b"play \"foo\\")
+            .unwrap_err()
+            .is_incomplete());
+        assert!(!super::client_command(b"play \"foo\n\"\n")
+            .unwrap_err()
+            .is_incomplete());
+        assert!(matches!(
+            super::client_command(b"find \"a\\\nclear\"\n"),
+            Err(NomErr::Failure(_))
+        ));
+        assert!(matches!(
+            super::client_command(b"find a\\\nclear\n"),
+            Err(NomErr::Failure(_))
+        ));
+        assert!(matches!(
+            super::client_command(b"find a\0b\n"),
+            Err(NomErr::Failure(_))
+        ));
+        assert!(matches!(
+            super::client_command(b"find \"a\\\0\"\n"),
+            Err(NomErr::Failure(_))
+        ));
+        assert!(!super::client_command(b"\"play\"\n")
+            .unwrap_err()
+            .is_incomplete());
+        assert!(!super::client_command(b"play foo\"bar\"\n")
+            .unwrap_err()
+            .is_incomplete());
+    }
+
     #[test]
     fn binary_field() {
         let keys = &mut HashSet::default();
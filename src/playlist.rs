@@ -0,0 +1,271 @@
+//! Importing playlist files.
+//!
+//! This supports the simple line-based M3U (including extended M3U) and PLS formats. The parsed
+//! entries can be resolved to song URIs relative to the music directory using [`resolve_entry`],
+//! and then added to the queue or a stored playlist with [`add_commands`].
+//!
+//! ```
+//! use mpd_protocol::playlist;
+//! use std::path::Path;
+//!
+//! let text = "#EXTM3U\n#EXTINF:123,Foo\n../Bar/01 Foo.flac\nhttp://example.com/stream\n";
+//! let entries = playlist::parse(text);
+//!
+//! let uris = entries.iter().filter_map(|entry| {
+//!     playlist::resolve_entry(entry, Path::new("/music/Playlists"), Path::new("/music"))
+//! });
+//!
+//! let lists = playlist::add_commands(uris, None, 1024 * 1024);
+//! assert_eq!(lists.len(), 1);
+//! assert_eq!(lists[0].len(), 2);
+//! ```
+
+use std::path::Path;
+
+use crate::command::{Command, CommandList};
+use crate::uri::{decode_url, song_uri};
+
+/// Parse the entries of a playlist file.
+///
+/// The format is detected automatically: files whose first non-empty line is `[playlist]` are
+/// treated as PLS, everything else as M3U. A leading byte order mark is ignored. Entries are
+/// returned as they appear in the file, use [`resolve_entry`] to turn them into song URIs.
+pub fn parse(text: &str) -> Vec<String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let first_line = text.lines().map(str::trim).find(|l| !l.is_empty());
+    let is_pls = matches!(first_line, Some(l) if l.eq_ignore_ascii_case("[playlist]"));
+
+    if is_pls {
+        parse_pls(text)
+    } else {
+        parse_m3u(text)
+    }
+}
+
+/// Parse the entries of an M3U playlist.
+///
+/// Empty lines and lines starting with `#` (comments and extended M3U directives) are skipped.
+pub fn parse_m3u(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Parse the entries of a PLS playlist.
+///
+/// Only the `FileN` keys are considered, and entries are returned ordered by their number.
+pub fn parse_pls(text: &str) -> Vec<String> {
+    let mut entries = text
+        .lines()
+        .filter_map(|l| {
+            let (key, value) = split_once(l, '=')?;
+            let key = key.trim();
+
+            match key.get(..4) {
+                Some(prefix) if prefix.eq_ignore_ascii_case("file") => {}
+                _ => return None,
+            }
+
+            let index = key[4..].parse::<u64>().ok()?;
+            Some((index, value.trim()))
+        })
+        .filter(|(_, value)| !value.is_empty())
+        .collect::<Vec<_>>();
+
+    entries.sort_by_key(|&(index, _)| index);
+    entries.into_iter().map(|(_, v)| String::from(v)).collect()
+}
+
+/// Resolve a playlist entry to a URI that can be passed to `add`.
+///
+/// Entries which look like URLs (i.e. contain `://`) are returned unchanged, except for `file://`
+/// URLs, which are decoded and treated like local paths. Relative paths are resolved against
+/// `playlist_dir`, the directory containing the playlist file. Local paths are
+/// then made relative to `music_root`, the music directory of the server, using
+/// [`song_uri`]. Note that this accesses the filesystem to resolve symbolic links.
+///
+/// Returns `None` if the entry refers to a local file outside of `music_root`, since those can
+/// not be added to the queue, or if it is a `file://` URL referring to another host.
+pub fn resolve_entry(entry: &str, playlist_dir: &Path, music_root: &Path) -> Option<String> {
+    let is_file_url = matches!(entry.get(..7), Some(s) if s.eq_ignore_ascii_case("file://"));
+
+    if is_file_url {
+        // Only `localhost` (or an empty host) refers to files which can be resolved
+        let url = &entry[7..];
+        let path = url.strip_prefix("localhost").unwrap_or(url);
+
+        if !path.starts_with('/') {
+            return None;
+        }
+
+        return song_uri(music_root, Path::new(&*decode_url(path)?));
+    } else if entry.contains("://") {
+        return Some(String::from(entry));
+    }
+
+    song_uri(music_root, &playlist_dir.join(entry))
+}
+
+/// Create command lists adding the given URIs to the queue, or to the stored playlist with the
+/// given name.
+///
+/// The commands are split into several lists, so that the commands in each list take up at most
+/// `max_size` bytes when sent to the server (unless a single command is already larger). MPD
+/// closes the connection if a command list exceeds its `max_command_list_size` setting, which
+/// is 2048 KiB by default.
+///
+/// Returns an empty `Vec` if there are no URIs.
+pub fn add_commands<I>(uris: I, playlist: Option<&str>, max_size: usize) -> Vec<CommandList>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut lists = Vec::new();
+    let mut current: Option<(CommandList, usize)> = None;
+
+    for uri in uris {
+        let command = match playlist {
+            Some(name) => Command::new("playlistadd")
+                .argument(String::from(name))
+                .argument(uri),
+            None => Command::new("add").argument(uri),
+        };
+        let size = command.rendered_length_hint();
+
+        match &mut current {
+            Some((list, list_size)) if *list_size + size <= max_size => {
+                list.add(command);
+                *list_size += size;
+            }
+            _ => {
+                lists.extend(current.take().map(|(list, _)| list));
+                current = Some((CommandList::new(command), size));
+            }
+        }
+    }
+
+    lists.extend(current.map(|(list, _)| list));
+    lists
+}
+
+fn split_once(s: &str, delim: char) -> Option<(&str, &str)> {
+    let i = s.find(delim)?;
+    Some((&s[..i], &s[i + delim.len_utf8()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn m3u() {
+        let text = "#EXTM3U\r\n#EXTINF:123,Foo - Bar\r\nFoo/Bar.mp3\r\n\r\n  http://a/b  \r\n";
+
+        assert_eq!(parse(text), ["Foo/Bar.mp3", "http://a/b"]);
+        assert_eq!(parse(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn pls() {
+        let text = "\n[playlist]\nFile2=b.ogg\nTitle2=B\nfile1 = a.ogg\nFile3=\nFileX=c\nNumberOfEntries=2\nVersion=2\n";
+
+        assert_eq!(parse(text), ["a.ogg", "b.ogg"]);
+
+        assert_eq!(parse("[playlist]\nAñño=x\nFileñ=y\n"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        assert_eq!(parse("\u{feff}#EXTM3U\na.ogg\n"), ["a.ogg"]);
+        assert_eq!(parse("\u{feff}[playlist]\nFile1=a.ogg\n"), ["a.ogg"]);
+    }
+
+    #[test]
+    fn resolve() {
+        let dir = Path::new("/srv/music/Playlists");
+        let root = Path::new("/srv/music/");
+
+        assert_eq!(
+            resolve_entry("../Foo/./Bar.flac", dir, root),
+            Some(String::from("Foo/Bar.flac"))
+        );
+        assert_eq!(
+            resolve_entry("Baz.flac", dir, root),
+            Some(String::from("Playlists/Baz.flac"))
+        );
+        assert_eq!(
+            resolve_entry("/srv/music/Foo/Bar.flac", dir, root),
+            Some(String::from("Foo/Bar.flac"))
+        );
+        assert_eq!(
+            resolve_entry("http://a/b c", dir, root),
+            Some(String::from("http://a/b c"))
+        );
+        assert_eq!(
+            resolve_entry("file:///srv/music/Foo/B%C3%A4r%20Baz.flac", dir, root),
+            Some(String::from("Foo/Bär Baz.flac"))
+        );
+        assert_eq!(
+            resolve_entry("FILE://localhost/srv/music/Foo.flac", dir, root),
+            Some(String::from("Foo.flac"))
+        );
+        assert_eq!(resolve_entry("file:///home/foo/bar.flac", dir, root), None);
+        assert_eq!(
+            resolve_entry("file://host/srv/music/a.flac", dir, root),
+            None
+        );
+        assert_eq!(resolve_entry("file:///srv/music/%FF", dir, root), None);
+        assert_eq!(resolve_entry("/home/foo/bar.flac", dir, root), None);
+        assert_eq!(resolve_entry("../..", dir, root), None);
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(add_commands(Vec::new(), None, 100), Vec::new());
+
+        let uris = vec![String::from("a b.flac"), String::from("c.flac")];
+
+        assert_eq!(
+            add_commands(uris.clone(), None, 100),
+            [CommandList::new(Command::new("add").argument("a b.flac"))
+                .command(Command::new("add").argument("c.flac"))]
+        );
+        assert_eq!(
+            add_commands(uris, Some("foo"), 100),
+            [CommandList::new(
+                Command::new("playlistadd")
+                    .argument("foo")
+                    .argument("a b.flac")
+            )
+            .command(
+                Command::new("playlistadd")
+                    .argument("foo")
+                    .argument("c.flac")
+            )]
+        );
+    }
+
+    #[test]
+    fn command_batches() {
+        let uris = || (0..5).map(|i| format!("{}.flac", i));
+        // `add 0.flac\n`
+        let size = 11;
+
+        let lists = add_commands(uris(), None, 2 * size);
+        assert_eq!(
+            lists.iter().map(CommandList::len).collect::<Vec<_>>(),
+            [2, 2, 1]
+        );
+        assert_eq!(
+            lists[2],
+            CommandList::new(Command::new("add").argument("4.flac"))
+        );
+
+        assert_eq!(add_commands(uris(), None, 5 * size).len(), 1);
+        assert_eq!(add_commands(uris(), None, 5 * size - 1).len(), 2);
+
+        // Commands larger than the limit are still sent on their own
+        assert_eq!(add_commands(uris(), None, 1).len(), 5);
+    }
+}
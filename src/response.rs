@@ -1,11 +1,13 @@
 //! Complete responses.
 
 pub mod frame;
+pub mod value;
 
-use bytes::{Buf, BytesMut};
-use hashbrown::HashSet;
+use bytes::{Buf, Bytes, BytesMut};
+use hashbrown::{HashMap, HashSet};
 use tracing::trace;
 
+use std::hash::Hasher;
 use std::iter::FusedIterator;
 use std::mem;
 use std::slice;
@@ -13,7 +15,10 @@ use std::sync::Arc;
 use std::vec;
 
 pub use frame::Frame;
+pub use value::{FieldError, FromFieldValue, Toggle};
 
+use crate::dump::ResponseDump;
+use crate::hash::Fnv1a;
 use crate::parser::ParsedComponent;
 use crate::MpdProtocolError;
 
@@ -21,6 +26,11 @@ use crate::MpdProtocolError;
 /// to individual commands, and optionally a single [error][Error].
 ///
 /// Since an error terminates a command list, there can only be one error in a response.
+///
+/// If a command list was started using `command_list_begin` (e.g. by a proxy or another client
+/// library) instead of the `command_list_ok_begin` used by this crate, the server does not
+/// separate the responses to the individual commands. The combined output is then parsed as a
+/// single frame containing the fields of all commands in order.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Response {
     /// The sucessful responses.
@@ -77,6 +87,46 @@ impl Response {
         // There is always at least one frame
         self.into_iter().next().unwrap()
     }
+
+    /// Compute a hash of the contents of this response.
+    ///
+    /// The hash covers all frames (see [`Frame::content_hash`]) and the error, if any. It is
+    /// stable across program runs, platforms and releases of this crate, so it can be used to
+    /// cheaply detect whether a response changed (e.g. repeated `status` polls).
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+
+        hasher.write_number(self.frames.len() as u64);
+        for frame in &self.frames {
+            frame.hash_contents(&mut hasher);
+        }
+
+        match &self.error {
+            Some(error) => {
+                hasher.write_number(1);
+                hasher.write_number(error.code);
+                hasher.write_number(error.command_index);
+                match &error.current_command {
+                    Some(command) => {
+                        hasher.write_number(1);
+                        hasher.write_prefixed(command.as_bytes());
+                    }
+                    None => hasher.write_number(0),
+                }
+                hasher.write_prefixed(error.message.as_bytes());
+            }
+            None => hasher.write_number(0),
+        }
+
+        hasher.finish()
+    }
+
+    /// Get a human-readable dump of this response.
+    ///
+    /// See the [`dump`](crate::dump) module for details.
+    pub fn dump(&self) -> ResponseDump<'_> {
+        ResponseDump::new(self)
+    }
 }
 
 pub(crate) type InternedKeys = HashSet<Arc<str>>;
@@ -84,7 +134,14 @@ pub(crate) type InternedKeys = HashSet<Arc<str>>;
 #[derive(Clone, Debug)]
 pub(crate) struct ResponseBuilder {
     fields: InternedKeys,
+    key_aliases: HashMap<Box<str>, Arc<str>>,
     state: ResponseState,
+    /// Raw bytes of the response in progress, `None` if raw responses are not retained.
+    raw: Option<BytesMut>,
+    /// Raw bytes of the most recently completed response.
+    completed_raw: Option<Bytes>,
+    /// If field values and error messages are omitted from log output.
+    redact_values: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -103,16 +160,50 @@ impl ResponseBuilder {
     pub(crate) fn new() -> Self {
         Self {
             fields: HashSet::default(),
+            key_aliases: HashMap::default(),
             state: ResponseState::Initial,
+            raw: None,
+            completed_raw: None,
+            redact_values: false,
+        }
+    }
+
+    /// Enable or disable omitting field values and error messages from log output.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn set_redact_values(&mut self, enabled: bool) {
+        self.redact_values = enabled;
+    }
+
+    /// Enable or disable retaining the raw bytes of parsed responses.
+    pub(crate) fn set_retain_raw(&mut self, enabled: bool) {
+        match (enabled, &self.raw) {
+            (true, None) => self.raw = Some(BytesMut::new()),
+            (false, Some(_)) => {
+                self.raw = None;
+                self.completed_raw = None;
+            }
+            _ => {}
         }
     }
 
+    /// Take the raw bytes of the most recently completed response, if they were retained.
+    pub(crate) fn take_raw(&mut self) -> Option<Bytes> {
+        self.completed_raw.take()
+    }
+
+    /// Replace the key `alias` with `canonical` in all fields parsed from now on.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn add_key_alias(&mut self, alias: &str, canonical: &str) {
+        let canonical = intern_key(&mut self.fields, canonical);
+        self.key_aliases.insert(Box::from(alias), canonical);
+    }
+
     pub(crate) fn parse(
         &mut self,
         src: &mut BytesMut,
     ) -> Result<Option<Response>, MpdProtocolError> {
         while !src.is_empty() {
-            let (remaining, component) = match ParsedComponent::parse(&src, &mut self.fields) {
+            let (remaining, component) = match ParsedComponent::parse(src, &mut self.fields) {
                 Err(e) if e.is_incomplete() => break,
                 Err(_) => return Err(MpdProtocolError::InvalidMessage),
                 Ok(p) => p,
@@ -121,6 +212,10 @@ impl ResponseBuilder {
             let msg_end = src.len() - remaining.len();
             let mut msg = src.split_to(msg_end);
 
+            if let Some(raw) = &mut self.raw {
+                raw.extend_from_slice(&msg);
+            }
+
             match component {
                 ParsedComponent::Field { key, value } => self.field(key, value),
                 ParsedComponent::BinaryField { data_length } => {
@@ -143,8 +238,19 @@ impl ResponseBuilder {
         self.state != ResponseState::Initial
     }
 
-    fn field(&mut self, key: Arc<str>, value: String) {
-        trace!(?key, ?value, "parsed field");
+    fn field(&mut self, mut key: Arc<str>, value: String) {
+        if self.redact_values {
+            trace!(?key, "parsed field");
+        } else {
+            trace!(?key, ?value, "parsed field");
+        }
+
+        if !self.key_aliases.is_empty() {
+            if let Some(canonical) = self.key_aliases.get(&*key) {
+                key = Arc::clone(canonical);
+            }
+        }
+
         match &mut self.state {
             ResponseState::Initial => {
                 let mut frame = Frame::empty();
@@ -193,8 +299,15 @@ impl ResponseBuilder {
         };
     }
 
+    fn finish_raw(&mut self) {
+        if let Some(raw) = &mut self.raw {
+            self.completed_raw = Some(raw.split().freeze());
+        }
+    }
+
     fn finish(&mut self) -> Response {
         trace!("finished response");
+        self.finish_raw();
         match mem::replace(&mut self.state, ResponseState::Initial) {
             ResponseState::Initial => Response::empty(),
             ResponseState::InProgress { current } => Response {
@@ -211,7 +324,17 @@ impl ResponseBuilder {
     }
 
     fn error(&mut self, error: Error) -> Response {
-        trace!(?error, "parsed error");
+        if self.redact_values {
+            // The message may contain URIs or other values
+            trace!(
+                code = error.code,
+                command_index = error.command_index,
+                "parsed error"
+            );
+        } else {
+            trace!(?error, "parsed error");
+        }
+        self.finish_raw();
         match mem::replace(&mut self.state, ResponseState::Initial) {
             ResponseState::Initial | ResponseState::InProgress { .. } => Response {
                 frames: Vec::new(),
@@ -358,6 +481,63 @@ pub struct Error {
     pub message: Box<str>,
 }
 
+/// `ACK_ERROR_NO_EXIST`, a requested resource does not exist.
+const ERROR_NO_EXIST: u64 = 50;
+/// `ACK_ERROR_SYSTEM`, a system call or I/O operation failed.
+const ERROR_SYSTEM: u64 = 52;
+/// `ACK_ERROR_PLAYLIST_LOAD`, loading a playlist failed.
+const ERROR_PLAYLIST_LOAD: u64 = 53;
+
+impl Error {
+    /// If the error indicates that a resource does not exist, get the kind of resource.
+    ///
+    /// This is based on the message text (e.g. `No such song`), and as such is a best-effort
+    /// extraction which may not recognize all messages.
+    ///
+    /// ```
+    /// # use mpd_protocol::response::Error;
+    /// let error = Error {
+    ///     code: 50,
+    ///     message: "No such playlist".into(),
+    ///     ..Error::default()
+    /// };
+    ///
+    /// assert_eq!(error.missing_resource(), Some("playlist"));
+    /// ```
+    pub fn missing_resource(&self) -> Option<&str> {
+        if self.code != ERROR_NO_EXIST {
+            return None;
+        }
+
+        let resource = self.message.strip_prefix("No such ")?.trim();
+
+        if resource.is_empty() {
+            None
+        } else {
+            Some(resource)
+        }
+    }
+
+    /// If the error relates to a specific file or URI which could not be accessed, get it.
+    ///
+    /// This returns the first quoted section of the message for errors indicating missing
+    /// resources or failed I/O (e.g. `Failed to open "http://example.com/stream"`). Like
+    /// [`Error::missing_resource`], this is a best-effort extraction.
+    pub fn failed_uri(&self) -> Option<&str> {
+        if !matches!(
+            self.code,
+            ERROR_NO_EXIST | ERROR_SYSTEM | ERROR_PLAYLIST_LOAD
+        ) {
+            return None;
+        }
+
+        let start = self.message.find('"')? + 1;
+        let len = self.message[start..].find('"')?;
+
+        Some(&self.message[start..start + len])
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -423,6 +603,42 @@ mod test {
         assert_eq!((0, Some(0)), iter.size_hint());
     }
 
+    #[test]
+    fn content_hash() {
+        let a = Response {
+            frames: vec![frame([("ab", "c")], None)],
+            error: None,
+        };
+        let b = Response {
+            frames: vec![frame([("a", "bc")], None)],
+            error: None,
+        };
+        let c = Response {
+            frames: vec![frame([("ab", "c")], None)],
+            error: Some(Error::default()),
+        };
+        let d = Response {
+            frames: vec![frame([("ab", "c")], Some(b""))],
+            error: None,
+        };
+
+        assert_eq!(a.content_hash(), a.clone().content_hash());
+        assert_ne!(a.content_hash(), b.content_hash());
+        assert_ne!(a.content_hash(), c.content_hash());
+        assert_ne!(a.content_hash(), d.content_hash());
+        assert_ne!(
+            Response::empty().content_hash(),
+            Response {
+                frames: vec![],
+                error: None,
+            }
+            .content_hash()
+        );
+
+        // The hash must not change between releases
+        assert_eq!(a.content_hash(), 0xf9f2_f1cd_9709_74f6);
+    }
+
     #[test]
     fn simple_response() {
         let mut io = BytesMut::from("foo: bar\nOK");
@@ -615,6 +831,23 @@ mod test {
         assert_eq!(builder.state, ResponseState::Initial);
     }
 
+    #[test]
+    fn command_list_without_separators() {
+        let mut io = BytesMut::from("foo: bar\nbaz: qux\nfoo: bar\nOK\n");
+
+        assert_eq!(
+            ResponseBuilder::new().parse(&mut io).unwrap(),
+            Some(Response {
+                frames: vec![frame(
+                    [("foo", "bar"), ("baz", "qux"), ("foo", "bar")],
+                    None
+                )],
+                error: None
+            })
+        );
+        assert!(io.is_empty());
+    }
+
     #[test]
     fn command_list_error() {
         let mut io = BytesMut::from("list_OK\n");
@@ -662,4 +895,71 @@ mod test {
 
         assert!(Arc::ptr_eq(&a, &b));
     }
+
+    #[test]
+    fn raw_responses() {
+        let mut io =
+            BytesMut::from("foo: bar\nbinary: 2\nAB\nOK\nlist_OK\nACK [5@1] {} x\npartial");
+        let mut builder = ResponseBuilder::new();
+        builder.set_retain_raw(true);
+
+        assert!(builder.parse(&mut io).unwrap().is_some());
+        assert_eq!(
+            builder.take_raw().unwrap(),
+            &b"foo: bar\nbinary: 2\nAB\nOK\n"[..]
+        );
+        assert_eq!(builder.take_raw(), None);
+
+        assert!(builder.parse(&mut io).unwrap().unwrap().is_error());
+        assert_eq!(
+            builder.take_raw().unwrap(),
+            &b"list_OK\nACK [5@1] {} x\n"[..]
+        );
+
+        assert!(builder.parse(&mut io).unwrap().is_none());
+        assert_eq!(builder.take_raw(), None);
+
+        builder.set_retain_raw(false);
+        io.extend_from_slice(b": foo\nOK\n");
+        assert!(builder.parse(&mut io).unwrap().is_some());
+        assert_eq!(builder.take_raw(), None);
+    }
+
+    #[test]
+    fn key_aliases() {
+        let mut io = BytesMut::from("Last-Modified: foo\nlast-modified: bar\nOK\n");
+        let mut builder = ResponseBuilder::new();
+        builder.add_key_alias("last-modified", "Last-Modified");
+
+        let mut resp = builder.parse(&mut io).unwrap().unwrap();
+        let mut fields = resp.frames.pop().unwrap().into_iter();
+
+        let (a, _) = fields.next().unwrap();
+        let (b, value) = fields.next().unwrap();
+
+        assert_eq!(&*b, "Last-Modified");
+        assert_eq!(value, "bar");
+        assert!(Arc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn error_text() {
+        let error = |code, message: &str| Error {
+            code,
+            message: message.into(),
+            ..Error::default()
+        };
+
+        assert_eq!(error(50, "No such song").missing_resource(), Some("song"));
+        assert_eq!(error(50, "No such ").missing_resource(), None);
+        assert_eq!(error(50, "Not found").missing_resource(), None);
+        assert_eq!(error(2, "No such song").missing_resource(), None);
+
+        assert_eq!(
+            error(52, "Failed to open \"/music/a.flac\"; No such file").failed_uri(),
+            Some("/music/a.flac")
+        );
+        assert_eq!(error(50, "Failed to open \"foo").failed_uri(), None);
+        assert_eq!(error(5, "unknown command \"foo\"").failed_uri(), None);
+    }
 }
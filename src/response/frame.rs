@@ -1,13 +1,18 @@
 //! A succesful response to a command.
 
 use bytes::BytesMut;
+use hashbrown::HashMap;
 
 use std::fmt;
+use std::hash::Hasher;
 use std::iter::FusedIterator;
 use std::slice;
 use std::sync::Arc;
 use std::vec;
 
+use super::value::{FieldError, FromFieldValue};
+use crate::hash::Fnv1a;
+
 /// A succesful response to a command.
 ///
 /// Consists of zero or more key-value pairs, where the keys are not unique, and optionally a
@@ -53,6 +58,25 @@ impl Frame {
         Fields(self.fields.0.iter())
     }
 
+    /// Returns an iterator over the keys of all key-value pairs in this frame, in the order they
+    /// appear in the response.
+    ///
+    /// Keys which appear multiple times are yielded once for every field they belong to. If keys
+    /// have been removed using [`Frame::get`], they will not appear.
+    pub fn keys(&self) -> Keys<'_> {
+        Keys(self.fields.0.iter())
+    }
+
+    /// Returns `true` if the frame contains a key-value pair with the given key.
+    ///
+    /// The key is case-sensitive.
+    pub fn contains_key<K>(&self, key: K) -> bool
+    where
+        K: AsRef<str>,
+    {
+        self.fields().any(|(k, _)| k == key.as_ref())
+    }
+
     /// Find the first key-value pair with the given key, and return a reference to its value.
     ///
     /// The key is case-sensitive.
@@ -64,6 +88,54 @@ impl Frame {
             .find_map(|(k, v)| if k == key.as_ref() { Some(v) } else { None })
     }
 
+    /// Find the first key-value pair with the given key, and parse its value.
+    ///
+    /// The key is case-sensitive. Returns `None` if the key is not present, or if its value fails
+    /// to parse.
+    ///
+    /// ```
+    /// # use mpd_protocol::sync::receive;
+    /// # use std::time::Duration;
+    /// let frame = receive(&b"volume: 50\nelapsed: 1.500\nOK\n"[..])
+    ///     .unwrap()
+    ///     .unwrap()
+    ///     .single_frame()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(frame.find_parsed::<u8, _>("volume"), Some(50));
+    /// assert_eq!(frame.find_parsed("elapsed"), Some(Duration::from_millis(1500)));
+    /// ```
+    pub fn find_parsed<T, K>(&self, key: K) -> Option<T>
+    where
+        T: FromFieldValue,
+        K: AsRef<str>,
+    {
+        self.find(key).and_then(T::from_field_value)
+    }
+
+    /// Find the first key-value pair with the given key, and parse its value.
+    ///
+    /// Like [`Frame::find_parsed`], but for fields that are required to be present. The key is
+    /// case-sensitive.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FieldError`] describing the problem if the key is not present, or if its value
+    /// fails to parse.
+    pub fn parse_field<T, K>(&self, key: K) -> Result<T, FieldError>
+    where
+        T: FromFieldValue,
+        K: AsRef<str>,
+    {
+        let key = key.as_ref();
+
+        let value = self.find(key).ok_or_else(|| FieldError::Missing {
+            field: Box::from(key),
+        })?;
+
+        T::from_field_value(value).ok_or_else(|| FieldError::invalid::<T>(key, value))
+    }
+
     /// Returns a reference to the binary blob in this frame, if there is one.
     ///
     /// If the binary blob has been removed using [`Frame::get_binary`], this will return `None`.
@@ -92,12 +164,189 @@ impl Frame {
         })
     }
 
+    /// Compute a hash of the contents of this frame.
+    ///
+    /// The hash covers the key-value pairs (in order) and the binary blob. Unlike the [`Hash`]
+    /// implementations in the standard library, the result is stable across program runs,
+    /// platforms and releases of this crate, so it can be used as a cheap identity for caching.
+    ///
+    /// [`Hash`]: std::hash::Hash
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        self.hash_contents(&mut hasher);
+        hasher.finish()
+    }
+
+    pub(super) fn hash_contents(&self, hasher: &mut Fnv1a) {
+        hasher.write_number(self.fields_len() as u64);
+
+        for (key, value) in self.fields() {
+            hasher.write_prefixed(key.as_bytes());
+            hasher.write_prefixed(value.as_bytes());
+        }
+
+        match self.binary() {
+            Some(binary) => {
+                hasher.write_number(1);
+                hasher.write_prefixed(binary);
+            }
+            None => hasher.write_number(0),
+        }
+    }
+
+    /// Compare the fields and binary blob of this frame with `other`.
+    ///
+    /// Since keys may appear multiple times, fields are matched up by their key and the order in
+    /// which they appear: The second `foo` field in this frame is compared to the second `foo`
+    /// field in `other`. Changes are returned in the order of the fields in this frame, followed
+    /// by fields which only appear in `other`.
+    ///
+    /// ```
+    /// # use mpd_protocol::response::{frame::FieldChange, Frame};
+    /// # fn diff(old: &Frame, new: &Frame) {
+    /// let diff = old.diff(new);
+    ///
+    /// for change in &diff.fields {
+    ///     if let FieldChange::Changed { key: "state", new, .. } = change {
+    ///         println!("playback state changed to {}", new);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn diff<'a>(&'a self, other: &'a Frame) -> FrameDiff<'a> {
+        let mut other_values: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (key, value) in other.fields() {
+            other_values.entry(key).or_default().push(value);
+        }
+
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        let mut fields = Vec::new();
+
+        for (key, value) in self.fields() {
+            let n = occurrences.entry(key).or_default();
+            let other_value = other_values.get(key).and_then(|v| v.get(*n));
+            *n += 1;
+
+            match other_value {
+                Some(&new) if new == value => {}
+                Some(&new) => fields.push(FieldChange::Changed {
+                    key,
+                    old: value,
+                    new,
+                }),
+                None => fields.push(FieldChange::Removed { key, value }),
+            }
+        }
+
+        for (key, value) in other.fields() {
+            let n = occurrences.entry(key).or_default();
+
+            if *n == 0 {
+                fields.push(FieldChange::Added { key, value });
+            } else {
+                *n -= 1;
+            }
+        }
+
+        FrameDiff {
+            fields,
+            binary_changed: self.binary() != other.binary(),
+        }
+    }
+
     /// Get the binary blob contained in this frame, if present.
     ///
     /// This will remove it from the frame, future calls to this method will return `None`.
     pub fn get_binary(&mut self) -> Option<BytesMut> {
         self.binary.take()
     }
+
+    /// Get the binary blob contained in this frame together with its MIME type, if present.
+    ///
+    /// The MIME type is taken from the `type` field, which the server sends alongside the binary
+    /// data for some commands (e.g. `readpicture`). This removes both the binary blob and the
+    /// `type` field from the frame. If there is no binary blob, the frame is left unchanged.
+    pub fn get_binary_blob(&mut self) -> Option<BinaryBlob> {
+        let data = self.get_binary()?;
+        let mime = self.get("type");
+
+        Some(BinaryBlob { mime, data })
+    }
+}
+
+/// A binary blob with its MIME type, as returned by [`Frame::get_binary_blob`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryBlob {
+    /// The MIME type of the data, if the server sent one.
+    pub mime: Option<String>,
+    /// The binary data.
+    pub data: BytesMut,
+}
+
+/// Differences between two frames, as returned by [`Frame::diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameDiff<'a> {
+    /// Fields which were added, removed or changed.
+    pub fields: Vec<FieldChange<'a>>,
+    /// If the binary blobs are different (including one of the frames not having one).
+    pub binary_changed: bool,
+}
+
+impl FrameDiff<'_> {
+    /// Returns `true` if the frames were equal.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && !self.binary_changed
+    }
+}
+
+impl fmt::Display for FrameDiff<'_> {
+    /// Renders one line per change, prefixed with `+` for added, `-` for removed and `~` for
+    /// changed fields.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.fields {
+            match change {
+                FieldChange::Added { key, value } => writeln!(f, "+ {}: {}", key, value)?,
+                FieldChange::Removed { key, value } => writeln!(f, "- {}: {}", key, value)?,
+                FieldChange::Changed { key, old, new } => {
+                    writeln!(f, "~ {}: {} -> {}", key, old, new)?
+                }
+            }
+        }
+
+        if self.binary_changed {
+            writeln!(f, "~ <binary>")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single changed field in a [`FrameDiff`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldChange<'a> {
+    /// The field only appears in the new frame.
+    Added {
+        /// Key of the field.
+        key: &'a str,
+        /// Value in the new frame.
+        value: &'a str,
+    },
+    /// The field only appears in the old frame.
+    Removed {
+        /// Key of the field.
+        key: &'a str,
+        /// Value in the old frame.
+        value: &'a str,
+    },
+    /// The field appears in both frames with different values.
+    Changed {
+        /// Key of the field.
+        key: &'a str,
+        /// Value in the old frame.
+        old: &'a str,
+        /// Value in the new frame.
+        new: &'a str,
+    },
 }
 
 impl fmt::Debug for Frame {
@@ -162,6 +411,34 @@ impl DoubleEndedIterator for Fields<'_> {
 
 impl FusedIterator for Fields<'_> {}
 
+/// Iterator returned by the [`Frame::keys`] method.
+#[derive(Debug)]
+pub struct Keys<'a>(slice::Iter<'a, Option<(Arc<str>, String)>>);
+
+impl<'a> Iterator for Keys<'a> {
+    type Item = &'a Arc<str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0.next() {
+            None => None,
+            Some(None) => self.next(),
+            Some(Some((k, _))) => Some(k),
+        }
+    }
+}
+
+impl DoubleEndedIterator for Keys<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self.0.next_back() {
+            None => None,
+            Some(None) => self.next_back(),
+            Some(Some((k, _))) => Some(k),
+        }
+    }
+}
+
+impl FusedIterator for Keys<'_> {}
+
 impl<'a> IntoIterator for &'a Frame {
     type Item = (&'a str, &'a str);
     type IntoIter = Fields<'a>;
@@ -257,6 +534,36 @@ mod tests {
         assert!(!frame.has_binary());
     }
 
+    #[test]
+    fn binary_blob() {
+        let mut frame = Frame {
+            fields: FieldsContainer(vec![
+                Some((Arc::from("size"), String::from("11"))),
+                Some((Arc::from("type"), String::from("image/png"))),
+            ]),
+            binary: Some(BytesMut::from("hello world")),
+        };
+
+        assert_eq!(
+            frame.get_binary_blob(),
+            Some(BinaryBlob {
+                mime: Some(String::from("image/png")),
+                data: BytesMut::from("hello world"),
+            })
+        );
+        assert_eq!(frame.find("type"), None);
+        assert_eq!(frame.find("size"), Some("11"));
+        assert_eq!(frame.get_binary_blob(), None);
+
+        let mut frame = Frame {
+            fields: FieldsContainer(vec![Some((Arc::from("type"), String::from("foo")))]),
+            binary: None,
+        };
+
+        assert_eq!(frame.get_binary_blob(), None);
+        assert_eq!(frame.find("type"), Some("foo"));
+    }
+
     #[test]
     fn accessors() {
         let mut frame = Frame {
@@ -272,12 +579,113 @@ mod tests {
         assert_eq!(frame.find("404"), None);
         assert_eq!(frame.find("HELLO"), None); // case-sensitive
 
+        assert!(frame.contains_key("hello"));
+        assert!(!frame.contains_key("404"));
+        assert!(!frame.contains_key("HELLO")); // case-sensitive
+
         assert_eq!(frame.get("hello"), Some(String::from("first value")));
         assert_eq!(frame.get("hello"), Some(String::from("second value")));
         assert_eq!(frame.get("hello"), None);
         assert_eq!(frame.get("Foo"), None); // case-sensitive
     }
 
+    #[test]
+    fn parsed_accessors() {
+        let frame = Frame {
+            fields: FieldsContainer(vec![
+                Some((Arc::from("volume"), String::from("50"))),
+                Some((Arc::from("random"), String::from("1"))),
+                Some((Arc::from("song"), String::from("foo"))),
+            ]),
+            binary: None,
+        };
+
+        assert_eq!(frame.find_parsed::<u8, _>("volume"), Some(50));
+        assert_eq!(frame.find_parsed::<bool, _>("random"), Some(true));
+        assert_eq!(frame.find_parsed::<u32, _>("song"), None);
+        assert_eq!(frame.find_parsed::<u32, _>("404"), None);
+
+        assert_eq!(frame.parse_field::<u8, _>("volume"), Ok(50));
+        assert_eq!(
+            frame.parse_field::<u32, _>("song"),
+            Err(FieldError::Invalid {
+                field: "song".into(),
+                value: "foo".into(),
+                expected: "u32",
+            })
+        );
+        assert_eq!(
+            frame.parse_field::<u32, _>("404"),
+            Err(FieldError::Missing {
+                field: "404".into()
+            })
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let fields = |fields: &[(&str, &str)]| {
+            FieldsContainer(
+                fields
+                    .iter()
+                    .map(|&(k, v)| Some((Arc::from(k), String::from(v))))
+                    .collect(),
+            )
+        };
+
+        let old = Frame {
+            fields: fields(&[
+                ("state", "play"),
+                ("volume", "50"),
+                ("tag", "a"),
+                ("tag", "b"),
+                ("song", "3"),
+            ]),
+            binary: None,
+        };
+        let new = Frame {
+            fields: fields(&[
+                ("volume", "50"),
+                ("state", "pause"),
+                ("tag", "a"),
+                ("error", "foo"),
+            ]),
+            binary: Some(BytesMut::from("hello")),
+        };
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.fields,
+            [
+                FieldChange::Changed {
+                    key: "state",
+                    old: "play",
+                    new: "pause"
+                },
+                FieldChange::Removed {
+                    key: "tag",
+                    value: "b"
+                },
+                FieldChange::Removed {
+                    key: "song",
+                    value: "3"
+                },
+                FieldChange::Added {
+                    key: "error",
+                    value: "foo"
+                },
+            ]
+        );
+        assert!(diff.binary_changed);
+        assert_eq!(
+            diff.to_string(),
+            "~ state: play -> pause\n- tag: b\n- song: 3\n+ error: foo\n~ <binary>\n"
+        );
+
+        assert!(old.diff(&old).is_empty());
+        assert_eq!(new.diff(&old).fields.len(), 4);
+    }
+
     #[test]
     fn iter() {
         let frame = Frame {
@@ -298,6 +706,27 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn keys() {
+        let mut frame = Frame {
+            fields: FieldsContainer(vec![
+                Some((Arc::from("hello"), String::from("first value"))),
+                Some((Arc::from("foo"), String::from("bar"))),
+                Some((Arc::from("hello"), String::from("second value"))),
+            ]),
+            binary: None,
+        };
+
+        let keys = frame.keys().map(|k| &**k).collect::<Vec<_>>();
+        assert_eq!(keys, ["hello", "foo", "hello"]);
+
+        frame.get("foo");
+        assert!(!frame.contains_key("foo"));
+
+        let keys = frame.keys().rev().map(|k| &**k).collect::<Vec<_>>();
+        assert_eq!(keys, ["hello", "hello"]);
+    }
+
     #[test]
     fn owned_iter() {
         let frame = Frame {
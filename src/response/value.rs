@@ -0,0 +1,231 @@
+//! Conversion of field values into Rust types.
+
+use std::any;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Types which can be parsed from the value of a field in a [`Frame`][super::Frame].
+///
+/// This is implemented for the basic types commonly found in responses, and can be implemented
+/// for your own types to use them with [`Frame::find_parsed`][super::Frame::find_parsed].
+pub trait FromFieldValue: Sized {
+    /// Parse the given field value.
+    ///
+    /// Returns `None` if the value is not valid for the type.
+    fn from_field_value(value: &str) -> Option<Self>;
+}
+
+/// Error returned when a required field is missing or has an invalid value, as returned by
+/// [`Frame::parse_field`][super::Frame::parse_field].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldError {
+    /// The field was not present in the frame.
+    Missing {
+        /// Key of the field.
+        field: Box<str>,
+    },
+    /// The value of the field could not be parsed.
+    Invalid {
+        /// Key of the field.
+        field: Box<str>,
+        /// The value that failed to parse.
+        value: Box<str>,
+        /// Name of the type the value was supposed to be parsed as.
+        expected: &'static str,
+    },
+}
+
+/// State of a boolean-like option, such as `repeat`, `random`, `single` or `consume` in the
+/// response to `status`, or `outputenabled` in the response to `outputs`.
+///
+/// Some options have a third `oneshot` state (e.g. `single` since MPD 0.21, and `consume` since
+/// 0.24), where the option is automatically disabled again after taking effect once. Parsing
+/// all of these fields as a `Toggle` behaves the same regardless of which states the server
+/// supports for a particular field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Toggle {
+    /// The option is disabled (`0`).
+    Off,
+    /// The option is enabled (`1`).
+    On,
+    /// The option is enabled until it takes effect once (`oneshot`).
+    Oneshot,
+}
+
+impl Toggle {
+    /// Returns `true` if the option is currently in effect, i.e. it is either on or in the
+    /// oneshot state.
+    pub fn is_enabled(self) -> bool {
+        self != Toggle::Off
+    }
+
+    /// Get the representation of the state used in the protocol.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Toggle::Off => "0",
+            Toggle::On => "1",
+            Toggle::Oneshot => "oneshot",
+        }
+    }
+}
+
+impl From<bool> for Toggle {
+    fn from(enabled: bool) -> Self {
+        if enabled {
+            Toggle::On
+        } else {
+            Toggle::Off
+        }
+    }
+}
+
+impl FieldError {
+    pub(super) fn invalid<T>(field: &str, value: &str) -> Self {
+        FieldError::Invalid {
+            field: field.into(),
+            value: value.into(),
+            expected: any::type_name::<T>(),
+        }
+    }
+
+    /// Get the key of the field which caused the error.
+    pub fn field(&self) -> &str {
+        match self {
+            FieldError::Missing { field } | FieldError::Invalid { field, .. } => field,
+        }
+    }
+}
+
+impl Error for FieldError {}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::Missing { field } => write!(f, "missing field {:?}", field),
+            FieldError::Invalid {
+                field,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid value {:?} for field {:?} (expected {})",
+                value, field, expected
+            ),
+        }
+    }
+}
+
+impl FromFieldValue for String {
+    fn from_field_value(value: &str) -> Option<Self> {
+        Some(String::from(value))
+    }
+}
+
+impl FromFieldValue for bool {
+    /// Booleans are represented as `0` or `1`.
+    fn from_field_value(value: &str) -> Option<Self> {
+        match value {
+            "0" => Some(false),
+            "1" => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl FromFieldValue for Toggle {
+    /// Accepts `0`, `1` and `oneshot`.
+    fn from_field_value(value: &str) -> Option<Self> {
+        match value {
+            "0" => Some(Toggle::Off),
+            "1" => Some(Toggle::On),
+            "oneshot" => Some(Toggle::Oneshot),
+            _ => None,
+        }
+    }
+}
+
+impl FromFieldValue for Duration {
+    /// Durations are represented as a (possibly fractional) number of seconds.
+    fn from_field_value(value: &str) -> Option<Self> {
+        let seconds = value.parse::<f64>().ok()?;
+
+        // This rejects negative, non-finite and too large values
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+}
+
+macro_rules! impl_from_str {
+    ($($t:ty),*) => {
+        $(
+            impl FromFieldValue for $t {
+                fn from_field_value(value: &str) -> Option<Self> {
+                    value.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_str!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(bool::from_field_value("1"), Some(true));
+        assert_eq!(bool::from_field_value("0"), Some(false));
+        assert_eq!(bool::from_field_value("true"), None);
+
+        assert_eq!(u32::from_field_value("42"), Some(42));
+        assert_eq!(u32::from_field_value("-1"), None);
+        assert_eq!(i32::from_field_value("-1"), Some(-1));
+        assert_eq!(f32::from_field_value("0.5"), Some(0.5));
+
+        assert_eq!(
+            Duration::from_field_value("12.345"),
+            Some(Duration::from_millis(12345))
+        );
+        assert_eq!(
+            Duration::from_field_value("3"),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(Duration::from_field_value("-3"), None);
+        assert_eq!(Duration::from_field_value("inf"), None);
+        assert_eq!(Duration::from_field_value("NaN"), None);
+        assert_eq!(Duration::from_field_value("1e30"), None);
+        assert_eq!(Duration::from_field_value("foo"), None);
+
+        assert_eq!(String::from_field_value("foo"), Some(String::from("foo")));
+    }
+
+    #[test]
+    fn toggle() {
+        assert_eq!(Toggle::from_field_value("0"), Some(Toggle::Off));
+        assert_eq!(Toggle::from_field_value("1"), Some(Toggle::On));
+        assert_eq!(Toggle::from_field_value("oneshot"), Some(Toggle::Oneshot));
+        assert_eq!(Toggle::from_field_value("2"), None);
+
+        assert!(Toggle::Oneshot.is_enabled());
+        assert!(!Toggle::from(false).is_enabled());
+        assert_eq!(Toggle::Oneshot.as_str(), "oneshot");
+    }
+
+    #[test]
+    fn error_display() {
+        let missing = FieldError::Missing {
+            field: "volume".into(),
+        };
+        assert_eq!(missing.field(), "volume");
+        assert_eq!(missing.to_string(), "missing field \"volume\"");
+
+        let invalid = FieldError::invalid::<u32>("song", "foo");
+        assert_eq!(invalid.field(), "song");
+        assert_eq!(
+            invalid.to_string(),
+            "invalid value \"foo\" for field \"song\" (expected u32)"
+        );
+    }
+}
@@ -1,10 +1,11 @@
 //! Basic facilities for using the protocol using synchronous IO.
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use tracing::{debug, error, span, trace, Level};
 
 use std::io::{self, BufRead, Write};
 
+use crate::command::CommandWithPayload;
 use crate::{parser, response::ResponseBuilder, Command, CommandList, MpdProtocolError, Response};
 
 /// Connect to a server using the given IO.
@@ -42,7 +43,41 @@ where
 ///
 /// This will return an error if reading from the IO returns an error, if EOF is encountered while
 /// in the middle of a response, or if the server sends an invalid response.
-pub fn receive<IO>(mut io: IO) -> Result<Option<Response>, MpdProtocolError>
+pub fn receive<IO>(io: IO) -> Result<Option<Response>, MpdProtocolError>
+where
+    IO: BufRead,
+{
+    receive_internal(io, &mut ResponseBuilder::new())
+}
+
+/// Read a complete response from the given IO, and also return the raw bytes it was parsed from.
+///
+/// This is useful for proxies and debugging tools which need to forward or log the exact form
+/// sent by the server.
+///
+/// # Errors
+///
+/// This will return an error under the same conditions as [`receive`].
+pub fn receive_with_raw<IO>(io: IO) -> Result<Option<(Response, Bytes)>, MpdProtocolError>
+where
+    IO: BufRead,
+{
+    let mut builder = ResponseBuilder::new();
+    builder.set_retain_raw(true);
+
+    match receive_internal(io, &mut builder)? {
+        Some(response) => {
+            let raw = builder.take_raw().expect("raw response not retained");
+            Ok(Some((response, raw)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn receive_internal<IO>(
+    mut io: IO,
+    response: &mut ResponseBuilder,
+) -> Result<Option<Response>, MpdProtocolError>
 where
     IO: BufRead,
 {
@@ -50,7 +85,6 @@ where
     let _enter = span.enter();
 
     let mut src = BytesMut::new();
-    let mut response = ResponseBuilder::new();
 
     loop {
         let read = read_until(&mut io, b'\n', &mut src)?;
@@ -76,6 +110,56 @@ where
     }
 }
 
+/// Read all responses from the given IO until it reaches EOF.
+///
+/// This is mostly useful for loading captured protocol output, such as test fixtures stored in
+/// files. The input must not contain the server greeting, use [`connect`] to consume it first if
+/// present. Empty lines between responses (e.g. a trailing empty line at the end of a file) are
+/// ignored.
+///
+/// Lines must be terminated by a single `\n` like in the output of the server, the input is not
+/// modified in any other way since it may contain binary data. In particular, files with `\r\n`
+/// line endings are rejected.
+///
+/// # Errors
+///
+/// This will return an error under the same conditions as [`receive`]. Responses read before the
+/// error occured are discarded.
+pub fn receive_all<IO>(mut io: IO) -> Result<Vec<Response>, MpdProtocolError>
+where
+    IO: BufRead,
+{
+    let mut responses = Vec::new();
+
+    loop {
+        skip_empty_lines(&mut io)?;
+
+        match receive(&mut io)? {
+            Some(response) => responses.push(response),
+            None => break,
+        }
+    }
+
+    Ok(responses)
+}
+
+/// Consume any empty lines at the current position.
+fn skip_empty_lines<R: BufRead>(r: &mut R) -> Result<(), io::Error> {
+    loop {
+        let empty = match r.fill_buf() {
+            Ok(available) => available.iter().take_while(|&&b| b == b'\n').count(),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        if empty == 0 {
+            return Ok(());
+        }
+
+        r.consume(empty);
+    }
+}
+
 /// Read all bytes into `buf` until the delimiter `byte` or EOF is reached.
 fn read_until<R: BufRead>(r: &mut R, delim: u8, buf: &mut BytesMut) -> Result<usize, io::Error> {
     // Adapted from implementation of standard library `BufRead::read_until`
@@ -135,6 +219,26 @@ where
     Ok(())
 }
 
+/// Send the given [`CommandWithPayload`] using the given IO.
+///
+/// # Errors
+///
+/// This will return an error if writing to the IO returns an error.
+pub fn send_with_payload<IO>(
+    mut io: IO,
+    command: CommandWithPayload,
+) -> Result<(), MpdProtocolError>
+where
+    IO: Write,
+{
+    let mut buf = BytesMut::new();
+    command.render(&mut buf);
+
+    io.write_all(&buf)?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,6 +268,51 @@ mod tests {
         assert_eq!(receive(&mut buf).unwrap(), None);
     }
 
+    #[test]
+    fn read_raw() {
+        let mut buf = Cursor::new("foo: bar\nbinary: 2\nAB\nOK\nOK\n");
+
+        let (resp, raw) = receive_with_raw(&mut buf).unwrap().unwrap();
+        assert_eq!(resp.single_frame().unwrap().find("foo"), Some("bar"));
+        assert_eq!(raw, &b"foo: bar\nbinary: 2\nAB\nOK\n"[..]);
+
+        let (resp, raw) = receive_with_raw(&mut buf).unwrap().unwrap();
+        assert_eq!(resp, Response::empty());
+        assert_eq!(raw, &b"OK\n"[..]);
+
+        assert_eq!(receive_with_raw(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn read_all() {
+        let buf = "foo: bar\nOK\nbinary: 3\nA\nB\nOK\nACK [5@0] {} unknown command \"x\"\n";
+        let responses = receive_all(buf.as_bytes()).unwrap();
+
+        assert_eq!(responses.len(), 3);
+        assert_eq!(
+            responses[0].clone().single_frame().unwrap().find("foo"),
+            Some("bar")
+        );
+        assert_eq!(
+            responses[1].clone().single_frame().unwrap().binary(),
+            Some(&b"A\nB"[..])
+        );
+        assert!(responses[2].is_error());
+
+        assert_eq!(receive_all("".as_bytes()).unwrap(), Vec::new());
+        assert_eq!(receive_all("\n\n".as_bytes()).unwrap(), Vec::new());
+        assert_eq!(receive_all("foo: bar\nOK\n\n".as_bytes()).unwrap().len(), 1);
+        assert_eq!(receive_all("OK\n\nOK\n".as_bytes()).unwrap().len(), 2);
+        assert_matches!(
+            receive_all("OK\r\nOK\r\n".as_bytes()),
+            Err(MpdProtocolError::InvalidMessage)
+        );
+        assert_matches!(
+            receive_all("OK\nfoo: bar\n".as_bytes()),
+            Err(MpdProtocolError::Io(_))
+        );
+    }
+
     #[test]
     fn write() {
         const GREETING: &[u8] = b"OK MPD 0.22.0\n";
@@ -174,6 +323,11 @@ mod tests {
         send(&mut io, Command::new("playid").argument("3")).unwrap();
 
         assert_eq!(&io.get_ref()[GREETING.len()..], b"playid 3\n");
+
+        let command = Command::new("hello").with_unchecked_payload(&b"\x00raw"[..]);
+        send_with_payload(&mut io, command).unwrap();
+
+        assert_eq!(&io.get_ref()[GREETING.len()..], b"playid 3\nhello\n\x00raw");
     }
 
     #[test]
@@ -0,0 +1,333 @@
+//! Helpers for song URIs and remote URLs.
+//!
+//! MPD passes URLs of remote resources (e.g. `http://` streams added using `add`) to the
+//! respective input plugin as-is, so they need to be properly percent-encoded by the client.
+//! Songs in the database are instead identified by plain relative paths, which must **not** be
+//! encoded. These can be converted to and from paths in the local filesystem using
+//! [`song_path`] and [`song_uri`].
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Percent-encode characters which may not appear in a URL.
+///
+/// This encodes spaces, control characters, non-ASCII characters and other characters which are
+/// never valid in URLs, but leaves reserved characters (such as `/`, `?` and `&`) alone, so it
+/// can be applied to a complete URL. Existing percent-encoded sequences are preserved, so
+/// applying this to an already encoded URL does not encode it a second time.
+///
+/// Returns a borrowed [`Cow`] if nothing needed to be encoded.
+///
+/// ```
+/// # use mpd_protocol::uri::encode_url;
+/// assert_eq!(
+///     encode_url("http://example.com/my stream?q=ä"),
+///     "http://example.com/my%20stream?q=%C3%A4"
+/// );
+/// assert_eq!(encode_url("http://example.com/a%20b"), "http://example.com/a%20b");
+/// ```
+pub fn encode_url(url: &str) -> Cow<'_, str> {
+    let bytes = url.as_bytes();
+
+    if !bytes
+        .iter()
+        .enumerate()
+        .any(|(i, &b)| needs_encoding(bytes, i, b))
+    {
+        return Cow::Borrowed(url);
+    }
+
+    let mut out = String::with_capacity(url.len() + 8);
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if needs_encoding(bytes, i, b) {
+            out.push('%');
+            out.push(char::from(HEX_DIGITS[usize::from(b >> 4)]));
+            out.push(char::from(HEX_DIGITS[usize::from(b & 0xf)]));
+        } else {
+            out.push(char::from(b));
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+/// Decode percent-encoded sequences in a URL.
+///
+/// Percent signs which are not followed by two hexadecimal digits are left unchanged. Returns
+/// `None` if the decoded URL is not valid UTF-8, and a borrowed [`Cow`] if there was nothing to
+/// decode.
+///
+/// ```
+/// # use mpd_protocol::uri::decode_url;
+/// assert_eq!(
+///     decode_url("http://example.com/my%20stream?q=%C3%A4").unwrap(),
+///     "http://example.com/my stream?q=ä"
+/// );
+/// assert_eq!(decode_url("100%"), Some("100%".into()));
+/// assert_eq!(decode_url("%FF"), None);
+/// ```
+pub fn decode_url(url: &str) -> Option<Cow<'_, str>> {
+    let bytes = url.as_bytes();
+
+    if !(0..bytes.len()).any(|i| percent_sequence(bytes, i).is_some()) {
+        return Some(Cow::Borrowed(url));
+    }
+
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if let Some(decoded) = percent_sequence(bytes, i) {
+            out.push(decoded);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(out).ok().map(Cow::Owned)
+}
+
+/// Get the location of a song in the database in the local filesystem.
+///
+/// `music_root` is the music directory of the server (e.g. as returned by the `config`
+/// command, or configured by the user). Returns `None` for remote URLs and for URIs which are
+/// not plain relative paths inside the music directory (e.g. absolute paths or paths containing
+/// `..`).
+///
+/// ```
+/// # use mpd_protocol::uri::song_path;
+/// # use std::path::Path;
+/// assert_eq!(
+///     song_path(Path::new("/music"), "Foo/Bär.flac"),
+///     Some(Path::new("/music/Foo/Bär.flac").to_path_buf())
+/// );
+/// assert_eq!(song_path(Path::new("/music"), "../etc/passwd"), None);
+/// assert_eq!(song_path(Path::new("/music"), "http://example.com/stream"), None);
+/// ```
+pub fn song_path(music_root: &Path, uri: &str) -> Option<PathBuf> {
+    if uri.is_empty() || uri.contains("://") {
+        return None;
+    }
+
+    let mut path = music_root.to_path_buf();
+
+    for segment in uri.split('/') {
+        let mut components = Path::new(segment).components();
+
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(segment)), None) => path.push(segment),
+            _ => return None,
+        }
+    }
+
+    Some(path)
+}
+
+/// Get the URI identifying the song at the given path in the database.
+///
+/// This is the reverse of [`song_path`]. MPD identifies songs by their path inside the music
+/// directory, even if it involves symbolic links, so symbolic links in `path` are not resolved.
+/// The only exception are `..` components following a symbolic link, which refer to the parent
+/// of the link target like they do in the filesystem. `music_root` itself may be a symbolic link
+/// (e.g. a symlinked music directory), in which case paths through its target are accepted as
+/// well.
+///
+/// Returns `None` if the path is not inside the music directory, or if it is not valid UTF-8.
+///
+/// ```
+/// # use mpd_protocol::uri::song_uri;
+/// # use std::path::Path;
+/// assert_eq!(
+///     song_uri(Path::new("/music/"), Path::new("/music/Foo/./Bär.flac")),
+///     Some(String::from("Foo/Bär.flac"))
+/// );
+/// ```
+pub fn song_uri(music_root: &Path, path: &Path) -> Option<String> {
+    let path = normalize(path);
+
+    relative_uri(&normalize(music_root), &path).or_else(|| {
+        let root = fs::canonicalize(music_root).ok()?;
+        relative_uri(&root, &path)
+    })
+}
+
+fn relative_uri(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+
+    let uri = relative
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?
+        .join("/");
+
+    Some(uri)
+}
+
+/// Normalize a path, removing `.` and resolving `..` components.
+///
+/// This works lexically, unless a `..` component follows a symbolic link. In that case the link is
+/// resolved first, since `..` refers to the parent of the link target.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                let is_symlink = fs::symlink_metadata(&out)
+                    .map(|m| m.file_type().is_symlink())
+                    .unwrap_or(false);
+
+                if is_symlink {
+                    if let Ok(target) = fs::canonicalize(&out) {
+                        out = target;
+                    }
+                }
+
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// If the byte at the given position needs to be encoded.
+fn needs_encoding(bytes: &[u8], i: usize, b: u8) -> bool {
+    match b {
+        b'%' => percent_sequence(bytes, i).is_none(),
+        b' ' | b'"' | b'<' | b'>' | b'\\' | b'^' | b'`' | b'{' | b'|' | b'}' => true,
+        _ => !b.is_ascii_graphic(),
+    }
+}
+
+/// Returns the decoded value if there is a valid percent-encoded sequence at the given position.
+fn percent_sequence(bytes: &[u8], i: usize) -> Option<u8> {
+    match bytes.get(i..i + 3)? {
+        [b'%', high, low] => Some((hex_value(*high)? << 4) | hex_value(*low)?),
+        _ => None,
+    }
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    char::from(digit).to_digit(16).map(|d| d as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn encode() {
+        assert_matches!(encode_url("http://example.com/a?b=c&d"), Cow::Borrowed(_));
+
+        assert_eq!(encode_url("http://a/b c"), "http://a/b%20c");
+        assert_eq!(encode_url("http://a/日本"), "http://a/%E6%97%A5%E6%9C%AC");
+        assert_eq!(encode_url("http://a/\"{x}\""), "http://a/%22%7Bx%7D%22");
+        assert_eq!(encode_url("100%"), "100%25");
+        assert_eq!(encode_url("100%2"), "100%252");
+        assert_eq!(encode_url("a%2fb"), "a%2fb");
+    }
+
+    #[test]
+    fn decode() {
+        assert_matches!(decode_url("http://example.com/a"), Some(Cow::Borrowed(_)));
+
+        assert_eq!(decode_url("a%20b%2Fc").unwrap(), "a b/c");
+        assert_eq!(decode_url("%E6%97%A5%E6%9C%AC").unwrap(), "日本");
+        assert_eq!(decode_url("%zz%2").unwrap(), "%zz%2");
+        assert_eq!(decode_url("%C3"), None);
+    }
+
+    #[test]
+    fn song_paths() {
+        let root = Path::new("/srv/music");
+
+        assert_eq!(
+            song_path(root, "a/b c.flac"),
+            Some(PathBuf::from("/srv/music/a/b c.flac"))
+        );
+        assert_eq!(song_path(root, "/etc/passwd"), None);
+        assert_eq!(song_path(root, "a/../../b"), None);
+        assert_eq!(song_path(root, "a//b"), None);
+        assert_eq!(song_path(root, ""), None);
+
+        assert_eq!(
+            song_uri(root, Path::new("/srv/music/a/../b.flac")),
+            Some(String::from("b.flac"))
+        );
+        assert_eq!(song_uri(root, Path::new("/srv/music")), None);
+        assert_eq!(song_uri(root, Path::new("/srv/musicfoo/a.flac")), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn symlinked_root() {
+        let dir = std::env::temp_dir().join(format!("mpd_protocol-uri-{}", std::process::id()));
+        let music = dir.join("music");
+        let link = dir.join("link");
+
+        fs::create_dir_all(music.join("a")).unwrap();
+        fs::write(music.join("a/b.flac"), b"").unwrap();
+        std::os::unix::fs::symlink(&music, &link).unwrap();
+
+        // `a/deep` points to `a/c/d`, so `a/deep/..` is `a/c` and not `a`
+        fs::create_dir_all(music.join("a/c/d")).unwrap();
+        fs::write(music.join("a/c/e.flac"), b"").unwrap();
+        std::os::unix::fs::symlink(music.join("a/c/d"), music.join("a/deep")).unwrap();
+
+        let uri = song_uri(&link, &music.join("a/b.flac"));
+        let through_link = song_uri(&link, &link.join("a/b.flac"));
+        let parent_of_symlink = song_uri(&music, &music.join("a/deep/../e.flac"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(uri, Some(String::from("a/b.flac")));
+        assert_eq!(through_link, Some(String::from("a/b.flac")));
+        assert_eq!(parent_of_symlink, Some(String::from("a/c/e.flac")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn symlinked_songs() {
+        let dir = std::env::temp_dir().join(format!("mpd_protocol-songs-{}", std::process::id()));
+        let music = dir.join("music");
+        let elsewhere = dir.join("elsewhere");
+
+        fs::create_dir_all(music.join("a")).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(music.join("a/b.flac"), b"").unwrap();
+        fs::write(elsewhere.join("c.flac"), b"").unwrap();
+
+        // Songs are identified by the path of the link, not the path of the target
+        std::os::unix::fs::symlink("../../elsewhere/c.flac", music.join("a/c.flac")).unwrap();
+        std::os::unix::fs::symlink("a/b.flac", music.join("b.flac")).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, music.join("Best")).unwrap();
+
+        let outside_file = song_uri(&music, &music.join("a/c.flac"));
+        let inside_file = song_uri(&music, &music.join("b.flac"));
+        let outside_dir = song_uri(&music, &music.join("Best/./c.flac"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(outside_file, Some(String::from("a/c.flac")));
+        assert_eq!(inside_file, Some(String::from("b.flac")));
+        assert_eq!(outside_dir, Some(String::from("Best/c.flac")));
+    }
+
+    #[test]
+    fn round_trip() {
+        let url = "http://example.com/Ä Ö/100%?x=\"1\"";
+        assert_eq!(decode_url(&encode_url(url)).unwrap(), url);
+    }
+}